@@ -0,0 +1,619 @@
+//! Bitmap fonts for the libnx console, loaded from PSF1, PSF2 or BDF files, or
+//! rasterised from a TTF. libnx's `font` module borrows them as a
+//! `ConsoleFont`. This is its own crate so that its tests run on the host:
+//! `cargo test -p nx-font --target x86_64-unknown-linux-gnu`.
+
+use rusttype::{point, Font, Scale};
+use std::convert::TryInto;
+use std::str;
+use thiserror::Error;
+
+const PSF1_MAGIC: &[u8] = &[0x36, 0x04];
+const PSF1_MODE_512: u8 = 0x01;
+const PSF2_MAGIC: &[u8] = &[0x72, 0xb5, 0x4a, 0x86];
+const BDF_MAGIC: &[u8] = b"STARTFONT";
+
+#[derive(Error, Debug, Clone, PartialEq, Eq, Hash)]
+pub enum FontError {
+    #[error("not a PSF1, PSF2 or BDF font")]
+    UnknownFormat,
+    #[error("font data ends early")]
+    Truncated,
+    #[error("{width}x{height} glyphs are too large")]
+    TooLarge { width: u32, height: u32 },
+    #[error("{width}x{height} glyphs are empty")]
+    Empty { width: u32, height: u32 },
+    #[error("BDF line {line}: {message}")]
+    Bdf { line: usize, message: String },
+}
+
+type Result<T> = std::result::Result<T, FontError>;
+
+/// The tile size of libnx's renderer. Smaller glyphs are padded to it.
+const LIBNX_TILE: u16 = 16;
+
+/// The number of `u16`s libnx's renderer reads for each glyph, whatever the
+/// tile size.
+const LIBNX_GLYPH_LEN: usize = 16;
+
+/// The most `u16`s a parsed font may take up, which is 32 MiB. BDF fonts
+/// aren't bounded by the size of the file, since their glyphs can be sparse.
+const MAX_LEN: usize = 16 << 20;
+
+/// The number of `u16`s in each row of a glyph tile.
+pub fn words_per_row(tile_width: u16) -> usize {
+    (usize::from(tile_width) + 15) / 16
+}
+
+/// The number of `u16`s in a font with `num_chars` glyph tiles. libnx's
+/// renderer reads 16 `u16`s for each glyph up to and including `num_chars`,
+/// which its bounds check lets through, so this is never less than that.
+pub fn min_len(num_chars: u16, tile_width: u16, tile_height: u16) -> usize {
+    let glyphs = usize::from(num_chars) * usize::from(tile_height) * words_per_row(tile_width);
+    glyphs.max((usize::from(num_chars) + 1) * LIBNX_GLYPH_LEN)
+}
+
+/// Glyph tiles in the layout libnx's `ConsoleFont` expects: each glyph is
+/// `tile_height` rows of `words_per_row(tile_width)` `u16`s, with the leftmost
+/// pixel in the top bit.
+///
+/// Glyphs up to 16x16 are padded to 16x16 tiles, which libnx's renderer can
+/// draw. Larger ones need a `ConsoleRender`, such as
+/// `fb_render::FramebufferRender`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct BitmapFont {
+    gfx: Vec<u16>,
+    ascii_offset: u16,
+    num_chars: u16,
+    width: u16,
+    height: u16,
+    tile_width: u16,
+    tile_height: u16,
+}
+
+impl BitmapFont {
+    /// A font with every glyph blank.
+    pub fn blank(ascii_offset: u16, num_chars: u16, width: u16, height: u16) -> Self {
+        let tile_width = width.max(LIBNX_TILE);
+        let tile_height = height.max(LIBNX_TILE);
+        let len = min_len(num_chars, tile_width, tile_height);
+        Self {
+            gfx: vec![0; len],
+            ascii_offset,
+            num_chars,
+            width,
+            height,
+            tile_width,
+            tile_height,
+        }
+    }
+
+    /// Like `blank`, but fails instead of allocating more than `MAX_LEN`.
+    fn try_blank(ascii_offset: u16, num_chars: u16, width: u16, height: u16) -> Result<Self> {
+        if min_len(num_chars, width.max(LIBNX_TILE), height.max(LIBNX_TILE)) > MAX_LEN {
+            return Err(FontError::TooLarge {
+                width: width.into(),
+                height: height.into(),
+            });
+        }
+        Ok(Self::blank(ascii_offset, num_chars, width, height))
+    }
+
+    /// Parses a PSF1, PSF2 or BDF font, depending on its header.
+    pub fn parse(data: &[u8]) -> Result<Self> {
+        if data.starts_with(PSF1_MAGIC) || data.starts_with(PSF2_MAGIC) {
+            Self::from_psf(data)
+        } else if data.starts_with(BDF_MAGIC) {
+            let text = str::from_utf8(data).map_err(|_| FontError::Bdf {
+                line: 0,
+                message: "not valid UTF-8".to_string(),
+            })?;
+            Self::from_bdf(text)
+        } else {
+            Err(FontError::UnknownFormat)
+        }
+    }
+
+    /// Parses a PSF1 or PSF2 font. Glyphs are indexed by their position in
+    /// the file, so the Unicode table is ignored.
+    pub fn from_psf(data: &[u8]) -> Result<Self> {
+        if data.starts_with(PSF1_MAGIC) {
+            let header = data.get(..4).ok_or(FontError::Truncated)?;
+            let num_chars = if header[2] & PSF1_MODE_512 != 0 {
+                512
+            } else {
+                256
+            };
+            let height = u32::from(header[3]);
+            Self::from_rows(&data[4..], num_chars, 8, height, height as usize)
+        } else if data.starts_with(PSF2_MAGIC) {
+            let field = |i: usize| -> Result<u32> {
+                let bytes = data.get(i * 4..i * 4 + 4).ok_or(FontError::Truncated)?;
+                Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+            };
+            let header_size = field(2)? as usize;
+            let num_chars = field(4)?;
+            let glyph_size = field(5)? as usize;
+            let height = field(6)?;
+            let width = field(7)?;
+            let glyphs = data.get(header_size..).ok_or(FontError::Truncated)?;
+            Self::from_rows(glyphs, num_chars, width, height, glyph_size)
+        } else {
+            Err(FontError::UnknownFormat)
+        }
+    }
+
+    /// Reads glyphs stored as rows of bytes, with the leftmost pixel in the
+    /// top bit, and each glyph starting `glyph_size` bytes after the last.
+    fn from_rows(
+        data: &[u8],
+        num_chars: u32,
+        width: u32,
+        height: u32,
+        glyph_size: usize,
+    ) -> Result<Self> {
+        if width == 0 || height == 0 {
+            return Err(FontError::Empty { width, height });
+        }
+        let too_large = FontError::TooLarge { width, height };
+        let num_chars = num_chars.try_into().map_err(|_| too_large.clone())?;
+        let width = width.try_into().map_err(|_| too_large.clone())?;
+        let height = height.try_into().map_err(|_| too_large)?;
+        let row_size = (usize::from(width) + 7) / 8;
+        if row_size * usize::from(height) > glyph_size {
+            return Err(FontError::Truncated);
+        }
+        // before allocating anything based on the header
+        let len = usize::from(num_chars).checked_mul(glyph_size);
+        if len.map_or(true, |len| data.len() < len) {
+            return Err(FontError::Truncated);
+        }
+
+        let mut font = Self::try_blank(0, num_chars, width, height)?;
+        for glyph in 0..num_chars {
+            let start = usize::from(glyph) * glyph_size;
+            let bitmap = data
+                .get(start..start + glyph_size)
+                .ok_or(FontError::Truncated)?;
+            for (y, row) in bitmap.chunks(row_size).take(height.into()).enumerate() {
+                font.blit_row(glyph, 0, y as i32, row, width.into());
+            }
+        }
+        Ok(font)
+    }
+
+    /// Parses a BDF font. Glyphs are indexed by their encoding, from the
+    /// lowest in the file up to the highest.
+    pub fn from_bdf(text: &str) -> Result<Self> {
+        struct Glyph {
+            encoding: u16,
+            bbx: [i32; 4],
+            rows: Vec<Vec<u8>>,
+        }
+
+        let mut bounding_box = None;
+        let mut glyphs = vec![];
+        let mut glyph: Option<Glyph> = None;
+        let mut in_bitmap = false;
+
+        for (i, line) in text.lines().enumerate() {
+            let error = |message: &str| FontError::Bdf {
+                line: i + 1,
+                message: message.to_string(),
+            };
+            let numbers = |words: &[&str], count: usize| -> Result<Vec<i32>> {
+                let numbers = words
+                    .iter()
+                    .map(|word| word.parse())
+                    .collect::<std::result::Result<Vec<i32>, _>>()
+                    .map_err(|_| error("expected a number"))?;
+                if numbers.len() < count {
+                    return Err(error("missing numbers"));
+                }
+                Ok(numbers)
+            };
+
+            let words = line.split_whitespace().collect::<Vec<_>>();
+            let (keyword, args) = match words.split_first() {
+                Some((keyword, args)) => (*keyword, args),
+                None => continue,
+            };
+
+            if in_bitmap {
+                if keyword == "ENDCHAR" {
+                    in_bitmap = false;
+                    glyphs.extend(glyph.take());
+                } else if let Some(current) = &mut glyph {
+                    let row = parse_hex(keyword).ok_or_else(|| error("bad bitmap row"))?;
+                    current.rows.push(row);
+                }
+                continue;
+            }
+
+            match keyword {
+                "FONTBOUNDINGBOX" => {
+                    let bbx = numbers(args, 4)?;
+                    bounding_box = Some([bbx[0], bbx[1], bbx[2], bbx[3]]);
+                }
+                "STARTCHAR" => {
+                    glyph = Some(Glyph {
+                        encoding: 0,
+                        bbx: bounding_box.ok_or_else(|| error("missing FONTBOUNDINGBOX"))?,
+                        rows: vec![],
+                    });
+                }
+                "ENCODING" => {
+                    let encoding = numbers(args, 1)?[0];
+                    let current = glyph
+                        .as_mut()
+                        .ok_or_else(|| error("ENCODING outside a glyph"))?;
+                    match encoding.try_into() {
+                        Ok(encoding) => current.encoding = encoding,
+                        // unencoded, or outside of what the console can index
+                        Err(_) => glyph = None,
+                    }
+                }
+                "BBX" => {
+                    let bbx = numbers(args, 4)?;
+                    if let Some(current) = &mut glyph {
+                        current.bbx = [bbx[0], bbx[1], bbx[2], bbx[3]];
+                    }
+                }
+                "BITMAP" => in_bitmap = true,
+                "ENDCHAR" => glyph = None,
+                _ => {}
+            }
+        }
+
+        let [width, height, x_offset, y_offset] = bounding_box.ok_or(FontError::Bdf {
+            line: 0,
+            message: "missing FONTBOUNDINGBOX".to_string(),
+        })?;
+        if width <= 0 || height <= 0 {
+            return Err(FontError::Empty {
+                width: width.max(0) as u32,
+                height: height.max(0) as u32,
+            });
+        }
+        let too_large = FontError::TooLarge {
+            width: width as u32,
+            height: height as u32,
+        };
+        let first = glyphs.iter().map(|glyph| glyph.encoding).min().unwrap_or(0);
+        let last = glyphs.iter().map(|glyph| glyph.encoding).max().unwrap_or(0);
+        let num_chars = (u32::from(last) - u32::from(first) + 1)
+            .try_into()
+            .map_err(|_| too_large.clone())?;
+        let cell_width = width.try_into().map_err(|_| too_large.clone())?;
+        let cell_height = height.try_into().map_err(|_| too_large)?;
+
+        let mut font = Self::try_blank(first, num_chars, cell_width, cell_height)?;
+        for glyph in &glyphs {
+            let [glyph_width, glyph_height, glyph_x, glyph_y] = glyph.bbx;
+            // BDF offsets are from the baseline, and y goes up
+            let left = glyph_x - x_offset;
+            let top = (height + y_offset) - (glyph_height + glyph_y);
+            for (y, row) in glyph.rows.iter().enumerate() {
+                font.blit_row(
+                    glyph.encoding - first,
+                    left,
+                    top + y as i32,
+                    row,
+                    glyph_width.max(0) as usize,
+                );
+            }
+        }
+        Ok(font)
+    }
+
+    /// Rasterises the Latin-1 characters of `font` into `width`x`height`
+    /// cells, scaled so that the ascent and descent fill the cell.
+    pub fn rasterize(font: &Font, width: u16, height: u16) -> Self {
+        let mut bitmap = Self::blank(0, 256, width, height);
+        let unit = font.v_metrics(Scale::uniform(1.0));
+        let scale = Scale::uniform(f32::from(height) / (unit.ascent - unit.descent));
+        let ascent = font.v_metrics(scale).ascent;
+
+        for code in 0..=255u8 {
+            let c = char::from(code);
+            if c.is_control() {
+                continue;
+            }
+            let glyph = font.glyph(c).scaled(scale).positioned(point(0.0, ascent));
+            if let Some(bounds) = glyph.pixel_bounding_box() {
+                glyph.draw(|x, y, coverage| {
+                    if coverage >= 0.5 {
+                        bitmap.set(
+                            code.into(),
+                            bounds.min.x + x as i32,
+                            bounds.min.y + y as i32,
+                        );
+                    }
+                });
+            }
+        }
+        bitmap
+    }
+
+    /// The glyph tiles, at least `min_len` `u16`s long.
+    pub fn gfx(&self) -> &[u16] {
+        &self.gfx
+    }
+
+    pub fn ascii_offset(&self) -> u16 {
+        self.ascii_offset
+    }
+
+    pub fn num_chars(&self) -> u16 {
+        self.num_chars
+    }
+
+    pub fn tile_width(&self) -> u16 {
+        self.tile_width
+    }
+
+    pub fn tile_height(&self) -> u16 {
+        self.tile_height
+    }
+
+    /// The width of the glyphs, which may be less than the font's tiles.
+    pub fn width(&self) -> u16 {
+        self.width
+    }
+
+    pub fn height(&self) -> u16 {
+        self.height
+    }
+
+    /// Sets a pixel, ignoring any outside the glyph's cell.
+    pub fn set(&mut self, glyph: u16, x: i32, y: i32) {
+        if glyph >= self.num_chars
+            || x < 0
+            || y < 0
+            || x >= i32::from(self.width)
+            || y >= i32::from(self.height)
+        {
+            return;
+        }
+        let (x, y) = (x as usize, y as usize);
+        let words = words_per_row(self.tile_width);
+        let row = usize::from(glyph) * usize::from(self.tile_height) + y;
+        self.gfx[row * words + x / 16] |= 0x8000 >> (x % 16);
+    }
+
+    /// Sets the pixels of a row of bytes, with the leftmost in the top bit.
+    fn blit_row(&mut self, glyph: u16, left: i32, y: i32, row: &[u8], width: usize) {
+        for x in 0..width.min(row.len() * 8) {
+            if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                self.set(glyph, left + x as i32, y);
+            }
+        }
+    }
+}
+
+fn parse_hex(digits: &str) -> Option<Vec<u8>> {
+    if digits.len() % 2 != 0 {
+        return None;
+    }
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(digits.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn get(font: &BitmapFont, glyph: u16, x: usize, y: usize) -> bool {
+        let words = words_per_row(font.tile_width);
+        let row = usize::from(glyph) * usize::from(font.tile_height) + y;
+        font.gfx[row * words + x / 16] & (0x8000 >> (x % 16)) != 0
+    }
+
+    fn psf1(mode: u8, height: u8, glyphs: &[u8]) -> Vec<u8> {
+        let mut data = vec![0x36, 0x04, mode, height];
+        data.extend_from_slice(glyphs);
+        data
+    }
+
+    fn psf2(num_chars: u32, glyph_size: u32, width: u32, height: u32, glyphs: &[u8]) -> Vec<u8> {
+        let mut data = PSF2_MAGIC.to_vec();
+        for field in &[0, 32, 0, num_chars, glyph_size, height, width] {
+            data.extend_from_slice(&u32::to_le_bytes(*field));
+        }
+        data.extend_from_slice(glyphs);
+        data
+    }
+
+    #[test]
+    fn psf1_glyphs() {
+        let mut glyphs = vec![0; 256 * 8];
+        glyphs[b'A' as usize * 8] = 0x80;
+        glyphs[b'A' as usize * 8 + 7] = 0x01;
+        let font = BitmapFont::parse(&psf1(0, 8, &glyphs)).unwrap();
+        assert_eq!((font.num_chars(), font.width(), font.height()), (256, 8, 8));
+        // padded for libnx
+        assert_eq!((font.tile_width(), font.tile_height()), (16, 16));
+        assert!(font.gfx().len() >= min_len(256, 16, 16));
+        assert!(get(&font, 65, 0, 0));
+        assert!(get(&font, 65, 7, 7));
+        assert!(!get(&font, 65, 1, 0));
+        assert!(!get(&font, 64, 0, 0));
+
+        let font = BitmapFont::parse(&psf1(PSF1_MODE_512, 1, &[0; 512])).unwrap();
+        assert_eq!(font.num_chars(), 512);
+    }
+
+    #[test]
+    fn psf1_errors() {
+        let truncated = psf1(0, 8, &[0; 256 * 8 - 1]);
+        assert_eq!(BitmapFont::parse(&truncated), Err(FontError::Truncated));
+        assert_eq!(
+            BitmapFont::parse(&[0x36, 0x04, 0]),
+            Err(FontError::Truncated)
+        );
+        assert_eq!(
+            BitmapFont::parse(&psf1(0, 0, &[])),
+            Err(FontError::Empty {
+                width: 8,
+                height: 0
+            })
+        );
+    }
+
+    #[test]
+    fn psf2_glyphs() {
+        // 10 pixels wide, so each row takes two bytes
+        let glyphs = [0, 0, 0, 0, 0x80, 0x00, 0x00, 0x40];
+        let font = BitmapFont::parse(&psf2(2, 4, 10, 2, &glyphs)).unwrap();
+        assert_eq!((font.num_chars(), font.width(), font.height()), (2, 10, 2));
+        assert!(get(&font, 1, 0, 0));
+        assert!(get(&font, 1, 9, 1));
+        assert!(!get(&font, 1, 1, 0));
+        assert!(!get(&font, 0, 0, 0));
+
+        let font = BitmapFont::parse(&psf2(1, 24 * 3, 24, 24, &[0xff; 72])).unwrap();
+        assert_eq!((font.tile_width(), font.tile_height()), (24, 24));
+        assert!(get(&font, 0, 23, 23));
+    }
+
+    #[test]
+    fn psf2_errors() {
+        assert_eq!(
+            BitmapFont::parse(&psf2(1, 4, 0, 2, &[0; 4])),
+            Err(FontError::Empty {
+                width: 0,
+                height: 2
+            })
+        );
+        // each glyph needs 4 bytes
+        assert_eq!(
+            BitmapFont::parse(&psf2(1, 3, 10, 2, &[0; 4])),
+            Err(FontError::Truncated)
+        );
+        assert_eq!(
+            BitmapFont::parse(&psf2(2, 4, 10, 2, &[0; 7])),
+            Err(FontError::Truncated)
+        );
+        assert_eq!(
+            BitmapFont::parse(&PSF2_MAGIC.repeat(4)),
+            Err(FontError::Truncated)
+        );
+    }
+
+    #[test]
+    fn psf2_oversized_header() {
+        let glyph_size = 8192 * 65535;
+        assert_eq!(
+            BitmapFont::parse(&psf2(65535, glyph_size, 65535, 65535, &[])),
+            Err(FontError::Truncated)
+        );
+        assert_eq!(
+            BitmapFont::parse(&psf2(1 << 16, 1, 8, 1, &[0; 1 << 16])),
+            Err(FontError::TooLarge {
+                width: 8,
+                height: 1
+            })
+        );
+    }
+
+    const BDF: &str = "STARTFONT 2.1
+FONTBOUNDINGBOX 8 8 0 -2
+STARTCHAR A
+ENCODING 65
+BBX 2 2 1 0
+BITMAP
+80
+40
+ENDCHAR
+STARTCHAR unencoded
+ENCODING -1
+BBX 8 8 0 -2
+BITMAP
+FF
+ENDCHAR
+STARTCHAR C
+ENCODING 67
+BITMAP
+00
+00
+00
+00
+00
+00
+00
+01
+ENDCHAR
+ENDFONT
+";
+
+    #[test]
+    fn bdf_glyphs() {
+        let font = BitmapFont::parse(BDF.as_bytes()).unwrap();
+        assert_eq!((font.ascii_offset(), font.num_chars()), (65, 3));
+        assert_eq!((font.width(), font.height()), (8, 8));
+        // 2 pixels above the bottom of the cell, which is 2 below the baseline
+        assert!(get(&font, 0, 1, 4));
+        assert!(get(&font, 0, 2, 5));
+        assert!(!get(&font, 0, 1, 5));
+        assert!(get(&font, 2, 7, 7));
+        assert!((0..8).all(|y| (0..8).all(|x| !get(&font, 1, x, y))));
+    }
+
+    #[test]
+    fn bdf_errors() {
+        let error = |line, message: &str| FontError::Bdf {
+            line,
+            message: message.to_string(),
+        };
+        assert_eq!(
+            BitmapFont::parse(b"STARTFONT 2.1\nENDFONT\n"),
+            Err(error(0, "missing FONTBOUNDINGBOX"))
+        );
+        assert_eq!(
+            BitmapFont::parse(BDF.replace("80", "8").as_bytes()),
+            Err(error(7, "bad bitmap row"))
+        );
+        assert_eq!(
+            BitmapFont::parse(BDF.replace("BBX 2 2 1 0", "BBX 2 2 x 0").as_bytes()),
+            Err(error(5, "expected a number"))
+        );
+        assert_eq!(
+            BitmapFont::parse(b"STARTFONT 2.1\nFONTBOUNDINGBOX 0 8 0 0\n"),
+            Err(FontError::Empty {
+                width: 0,
+                height: 8
+            })
+        );
+        assert_eq!(
+            BitmapFont::parse(b"STARTFONT\xff"),
+            Err(error(0, "not valid UTF-8"))
+        );
+    }
+
+    #[test]
+    fn bdf_oversized() {
+        let font = "STARTFONT 2.1
+FONTBOUNDINGBOX 65535 65535 0 0
+STARTCHAR first
+ENCODING 0
+ENDCHAR
+STARTCHAR last
+ENCODING 65534
+ENDCHAR
+";
+        assert_eq!(
+            BitmapFont::parse(font.as_bytes()),
+            Err(FontError::TooLarge {
+                width: 65535,
+                height: 65535
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_format() {
+        assert_eq!(BitmapFont::parse(b"\x89PNG"), Err(FontError::UnknownFormat));
+    }
+}
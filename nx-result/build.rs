@@ -0,0 +1,450 @@
+use phf_shared::{FmtConst, PhfHash};
+use proc_macro2::Literal;
+use pylit::{parse_assignment, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+fn table(src: &str, name: &str) -> Value {
+    parse_assignment(src, name)
+        .unwrap_or_else(|err| panic!("failed to parse `{}` in assets/errcodes.py:{}", name, err))
+}
+
+fn expect_int(value: &Value, table: &str) -> u32 {
+    match value {
+        Value::Int(n) if *n >= 0 && *n <= i64::from(u32::MAX) => *n as u32,
+        Value::Int(n) => panic!("{}: {} does not fit in a u32", table, n),
+        other => panic!("{}: expected an int, found {}", table, other.type_name()),
+    }
+}
+
+fn expect_str<'a>(value: &'a Value, table: &str) -> &'a str {
+    match value {
+        Value::Str(s) => s,
+        other => panic!("{}: expected a str, found {}", table, other.type_name()),
+    }
+}
+
+fn expect_list<'a>(value: &'a Value, table: &str) -> &'a [Value] {
+    match value {
+        Value::List(items) => items,
+        other => panic!("{}: expected a list, found {}", table, other.type_name()),
+    }
+}
+
+fn expect_dict<'a>(value: &'a Value, table: &str) -> &'a [(Value, Value)] {
+    match value {
+        Value::Dict(entries) => entries,
+        other => panic!("{}: expected a dict, found {}", table, other.type_name()),
+    }
+}
+
+fn int_table<'a>(value: &'a Value, name: &str) -> Vec<(u32, &'a str)> {
+    expect_dict(value, name)
+        .iter()
+        .map(|(k, v)| (expect_int(k, name), expect_str(v, name).trim()))
+        .collect()
+}
+
+fn str_table<'a>(value: &'a Value, name: &str) -> Vec<(&'a str, &'a str)> {
+    expect_dict(value, name)
+        .iter()
+        .map(|(k, v)| (expect_str(k, name).trim(), expect_str(v, name).trim()))
+        .collect()
+}
+
+fn range_table<'a>(value: &'a Value, name: &str) -> Vec<(u32, u32, u32, &'a str)> {
+    let mut ranges = Vec::new();
+    for (module, entries) in expect_dict(value, name) {
+        let module = expect_int(module, name);
+        for entry in expect_list(entries, name) {
+            match expect_list(entry, name) {
+                [start, end, message] => ranges.push((
+                    module,
+                    expect_int(start, name),
+                    expect_int(end, name),
+                    expect_str(message, name)
+                        .trim()
+                        .trim_start_matches("Error: "),
+                )),
+                other => panic!(
+                    "{}: expected [start, end, message], found {} items",
+                    name,
+                    other.len()
+                ),
+            }
+        }
+    }
+    ranges
+}
+
+fn write_map<'a, K: Hash + Eq + PhfHash + FmtConst>(
+    file: &mut impl Write,
+    name: &str,
+    key_type: &str,
+    entries: impl DoubleEndedIterator<Item = (K, &'a str)>,
+) {
+    let mut map = phf_codegen::Map::new();
+
+    let deduped: HashMap<_, _> = entries.rev().collect();
+    for (k, v) in deduped {
+        map.entry(k, &Literal::string(v).to_string());
+    }
+
+    writeln!(
+        file,
+        "static {}: phf::Map<{}, &'static str> = \n{};\n",
+        name,
+        key_type,
+        map.build(),
+    )
+    .unwrap();
+}
+
+fn write_ranges<'a>(
+    file: &mut impl Write,
+    name: &str,
+    ranges: impl Iterator<Item = (u32, u32, u32, &'a str)>,
+) {
+    writeln!(file, "static {}: &[(u32, u32, u32, &str)] = &[", name).unwrap();
+    for (module, start, end, message) in ranges {
+        writeln!(
+            file,
+            "    ({}, {}, {}, {}),",
+            module,
+            start,
+            end,
+            Literal::string(message)
+        )
+        .unwrap();
+    }
+    writeln!(file, "];\n").unwrap();
+}
+
+/// Splits a word where its case changes, keeping acronyms together, so that
+/// "PartitionFS" becomes "Partition" and "FS", and "IStorage" becomes "I" and
+/// "Storage". Words are ASCII, so chars and bytes line up.
+fn split_case(word: &str) -> Vec<&str> {
+    let chars: Vec<char> = word.chars().collect();
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for i in 1..chars.len() {
+        // an acronym followed by a word, but not a plural like "NROs"
+        let acronym_end = chars[i - 1].is_ascii_uppercase()
+            && i + 1 < chars.len()
+            && chars[i + 1].is_ascii_lowercase()
+            && &word[i + 1..] != "s";
+        if chars[i].is_ascii_uppercase() && (chars[i - 1].is_ascii_lowercase() || acronym_end) {
+            parts.push(&word[start..i]);
+            start = i;
+        }
+    }
+    parts.push(&word[start..]);
+    parts
+}
+
+/// Turns free-form text into an UpperCamelCase identifier, e.g.
+/// "Invalid handle. " into `InvalidHandle` and "PartitionFS hash" into
+/// `PartitionFsHash`.
+fn camel_case(text: &str) -> String {
+    let mut out = String::new();
+    let text = text.replace('\'', "");
+    let words = text
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty());
+    for part in words.flat_map(split_case) {
+        let mut chars = part.chars();
+        out.push(chars.next().unwrap().to_ascii_uppercase());
+        out.extend(chars.map(|c| c.to_ascii_lowercase()));
+    }
+    if out.starts_with(|c: char| c.is_ascii_digit()) {
+        out.insert(0, 'E');
+    }
+    out
+}
+
+/// Names longer than this are cut before the first of `CLAUSE_WORDS`.
+const MAX_WORDS: usize = 6;
+
+/// Words that start a subordinate clause, which names can do without.
+const CLAUSE_WORDS: &[&str] = &[
+    "when", "while", "because", "during", "which", "that", "after", "before", "since", "unless",
+    "if", "due", "but",
+];
+
+/// Names a variant after the first clause of its message, so that
+/// "Time out. Also when you give 0 handles to ..." becomes `TimeOut` and
+/// "Version check failed when mounting ..." becomes `VersionCheckFailed`.
+fn variant_name(message: &str) -> String {
+    let message = message
+        .trim_start_matches("Error:")
+        .replace(" - ", ".")
+        .replace(" / ", ".");
+    let clause = message
+        .split(|c| ".,;:?!(".contains(c))
+        .map(str::trim)
+        .find(|clause| !clause.is_empty())
+        .unwrap_or("");
+    // libnx's own codes are named like `LibnxError_InitFail_FS`
+    let clause = match clause.find('_') {
+        Some(i) if !clause.contains(' ') => &clause[i + 1..],
+        _ => clause,
+    };
+    let mut words: Vec<_> = clause.split_whitespace().collect();
+    if words.len() > MAX_WORDS {
+        let end = words
+            .iter()
+            .skip(3)
+            .position(|word| CLAUSE_WORDS.contains(&&*word.to_ascii_lowercase()))
+            .map_or(words.len(), |i| i + 3);
+        words.truncate(end);
+    }
+    camel_case(&words.join(" "))
+}
+
+/// Names for the variants that libnx matches on, as `(module, description,
+/// variant)`, so that rewording their messages in errcodes.py doesn't rename
+/// them.
+const VARIANT_NAMES: &[(u32, u32, &str)] = &[
+    (1, 101, "InvalidSize"),
+    (1, 102, "InvalidAddress"),
+    (1, 114, "InvalidHandle"),
+    (1, 116, "InvalidCombination"),
+    (1, 117, "TimeOut"),
+    (1, 118, "OperationCanceled"),
+    (1, 119, "OutOfRange"),
+    (1, 120, "InvalidEnum"),
+    (1, 121, "NoSuchEntry"),
+    (1, 123, "PortRemoteDead"),
+    (1, 131, "PortClosed"),
+    (2, 1, "PathDoesNotExist"),
+    (2, 2, "PathAlreadyExists"),
+    (2, 60, "SpecifiedMountNameAlreadyExists"),
+    (2, 1002, "TitleIdNotFoundSavedataNotFound"),
+    (2, 3003, "FileDirectoryAlreadyExists"),
+    (2, 5301, "MountpointNotFound"),
+    (2, 6400, "PermissionDenied"),
+    (2, 6602, "FileNotFound"),
+    (2, 6603, "DirectoryNotFound"),
+    (2, 6905, "MountNameNotFoundInTable"),
+];
+
+/// Names for modules whose names in `switch_modules` don't make good
+/// identifiers, as `(module, ResultKind variant, enum)`. libnx's own module
+/// would get `LibnxError`, which result.rs already uses.
+const KIND_NAMES: &[(u32, &str, &str)] = &[(345, "Libnx", "LibnxCoreError")];
+
+/// Generates an enum per module with known descriptions, plus `ResultKind`
+/// to dispatch between them.
+fn write_kinds(file: &mut impl Write, modules: &[(u32, &str)], errcodes: &[(u32, &str)]) {
+    let mut descriptions: BTreeMap<u32, BTreeMap<u32, &str>> = BTreeMap::new();
+    for &(code, message) in errcodes {
+        let module = code & 0x1FF;
+        let description = (code >> 9) & 0x1FFF;
+        // codes with reserved bits set wouldn't round-trip
+        if code != module | (description << 9) {
+            continue;
+        }
+        descriptions
+            .entry(module)
+            .or_default()
+            .entry(description)
+            .or_insert(message);
+    }
+
+    let mut names = HashSet::new();
+    let mut kinds = Vec::new();
+    for &(module, module_name) in modules {
+        let known = match descriptions.get(&module) {
+            Some(known) if !module_name.is_empty() => known,
+            _ => continue,
+        };
+        let (name, enum_name) = match KIND_NAMES.iter().find(|&&(m, _, _)| m == module) {
+            Some(&(_, name, enum_name)) => (name.to_string(), enum_name.to_string()),
+            None => {
+                let mut name = camel_case(module_name.split('(').next().unwrap());
+                if !names.insert(name.clone()) {
+                    name = format!("{}{}", name, module);
+                }
+                let enum_name = format!("{}Error", name);
+                (name, enum_name)
+            }
+        };
+
+        let pinned = |description: u32| {
+            VARIANT_NAMES
+                .iter()
+                .find(|&&(m, d, _)| m == module && d == description)
+                .map(|&(_, _, name)| name.to_string())
+        };
+        let mut variants: Vec<_> = known
+            .iter()
+            .map(|(&description, &message)| {
+                let name = pinned(description);
+                let is_pinned = name.is_some();
+                let name = name.unwrap_or_else(|| variant_name(message));
+                (description, message, name, is_pinned)
+            })
+            .collect();
+        let mut counts = HashMap::new();
+        for (_, _, variant, _) in &variants {
+            *counts.entry(variant.clone()).or_insert(0) += 1;
+        }
+        // pinned names never change, so anything clashing with them gives way
+        for (description, _, variant, is_pinned) in &mut variants {
+            if !*is_pinned
+                && (variant.is_empty() || variant == "Other" || counts[variant.as_str()] > 1)
+            {
+                *variant = format!("{}{}", variant, description);
+            }
+        }
+
+        writeln!(
+            file,
+            "/// Known descriptions for the {} module.",
+            module_name
+        )
+        .unwrap();
+        writeln!(file, "#[allow(clippy::enum_variant_names)]").unwrap();
+        writeln!(file, "#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]").unwrap();
+        writeln!(file, "pub enum {} {{", enum_name).unwrap();
+        for (_, message, variant, _) in &variants {
+            writeln!(file, "    #[doc = {}]", Literal::string(message)).unwrap();
+            writeln!(file, "    {},", variant).unwrap();
+        }
+        writeln!(file, "    /// A description without a known meaning.").unwrap();
+        writeln!(file, "    Other(u32),").unwrap();
+        writeln!(file, "}}\n").unwrap();
+
+        writeln!(file, "impl {} {{", enum_name).unwrap();
+        writeln!(file, "    pub const MODULE: u32 = {};\n", module).unwrap();
+        writeln!(
+            file,
+            "    pub fn from_description(description: u32) -> Self {{"
+        )
+        .unwrap();
+        writeln!(file, "        match description {{").unwrap();
+        for (description, _, variant, _) in &variants {
+            writeln!(file, "            {} => Self::{},", description, variant).unwrap();
+        }
+        writeln!(file, "            other => Self::Other(other),").unwrap();
+        writeln!(file, "        }}\n    }}\n").unwrap();
+        writeln!(file, "    pub fn description(self) -> u32 {{").unwrap();
+        writeln!(file, "        match self {{").unwrap();
+        for (description, _, variant, _) in &variants {
+            writeln!(file, "            Self::{} => {},", variant, description).unwrap();
+        }
+        writeln!(file, "            Self::Other(other) => other,").unwrap();
+        writeln!(file, "        }}\n    }}\n}}\n").unwrap();
+
+        writeln!(file, "impl From<{}> for ResultCode {{", enum_name).unwrap();
+        writeln!(file, "    fn from(error: {}) -> Self {{", enum_name).unwrap();
+        writeln!(
+            file,
+            "        Self::new({}::MODULE, error.description())",
+            enum_name
+        )
+        .unwrap();
+        writeln!(file, "    }}\n}}\n").unwrap();
+
+        kinds.push((module, module_name, name, enum_name));
+    }
+
+    writeln!(file, "/// A result code, decoded by module.").unwrap();
+    writeln!(file, "#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]").unwrap();
+    writeln!(file, "pub enum ResultKind {{").unwrap();
+    for (_, module_name, name, enum_name) in &kinds {
+        writeln!(file, "    /// An error from the {} module.", module_name).unwrap();
+        writeln!(file, "    {}({}),", name, enum_name).unwrap();
+    }
+    writeln!(
+        file,
+        "    /// An error from a module without known descriptions."
+    )
+    .unwrap();
+    writeln!(file, "    Unknown(ResultCode),").unwrap();
+    writeln!(file, "}}\n").unwrap();
+
+    writeln!(file, "impl From<ResultCode> for ResultKind {{").unwrap();
+    writeln!(file, "    fn from(code: ResultCode) -> Self {{").unwrap();
+    writeln!(
+        file,
+        "        if code != ResultCode::new(code.module(), code.description()) {{"
+    )
+    .unwrap();
+    writeln!(file, "            return Self::Unknown(code);").unwrap();
+    writeln!(file, "        }}").unwrap();
+    writeln!(file, "        match code.module() {{").unwrap();
+    for (module, _, name, enum_name) in &kinds {
+        writeln!(
+            file,
+            "            {} => Self::{}({}::from_description(code.description())),",
+            module, name, enum_name
+        )
+        .unwrap();
+    }
+    writeln!(file, "            _ => Self::Unknown(code),").unwrap();
+    writeln!(file, "        }}\n    }}\n}}\n").unwrap();
+
+    writeln!(file, "impl From<ResultKind> for ResultCode {{").unwrap();
+    writeln!(file, "    fn from(kind: ResultKind) -> Self {{").unwrap();
+    writeln!(file, "        match kind {{").unwrap();
+    for (_, _, name, _) in &kinds {
+        writeln!(
+            file,
+            "            ResultKind::{}(error) => error.into(),",
+            name
+        )
+        .unwrap();
+    }
+    writeln!(file, "            ResultKind::Unknown(code) => code,").unwrap();
+    writeln!(file, "        }}\n    }}\n}}\n").unwrap();
+}
+
+fn main() {
+    let path = Path::new(&env::var("OUT_DIR").unwrap()).join("codegen.rs");
+    let mut file = BufWriter::new(File::create(&path).unwrap());
+
+    let errcodes = include_str!("assets/errcodes.py");
+    let known_errcodes = table(errcodes, "switch_known_errcodes");
+    let modules = table(errcodes, "switch_modules");
+    let ranges = table(errcodes, "switch_known_errcode_ranges");
+    let support_pages = table(errcodes, "switch_support_page");
+    let game_errors = table(errcodes, "switch_game_err");
+
+    let known_errcodes = int_table(&known_errcodes, "switch_known_errcodes");
+    let modules = int_table(&modules, "switch_modules");
+
+    write_map(
+        &mut file,
+        "ERROR_CODES",
+        "u32",
+        known_errcodes.iter().copied(),
+    );
+    write_map(&mut file, "MODULE_NAMES", "u32", modules.iter().copied());
+    write_map(
+        &mut file,
+        "SUPPORT_PAGES",
+        "&'static str",
+        str_table(&support_pages, "switch_support_page").into_iter(),
+    );
+    write_map(
+        &mut file,
+        "GAME_ERRORS",
+        "&'static str",
+        str_table(&game_errors, "switch_game_err").into_iter(),
+    );
+
+    // some ranges link to a support page instead of describing the error
+    let (support_ranges, error_ranges): (Vec<_>, Vec<_>) =
+        range_table(&ranges, "switch_known_errcode_ranges")
+            .into_iter()
+            .partition(|(_, _, _, message)| message.starts_with("http"));
+    write_ranges(&mut file, "ERROR_RANGES", error_ranges.into_iter());
+    write_ranges(&mut file, "SUPPORT_RANGES", support_ranges.into_iter());
+
+    write_kinds(&mut file, &modules, &known_errcodes);
+}
@@ -0,0 +1,59 @@
+//! Decodes Horizon result codes on the host, using the same tables as the
+//! on-device `Display` impl.
+//!
+//! `cargo run -p nx-result --target x86_64-unknown-linux-gnu -- 2002-4301`
+
+use nx_result::{game_error, ResultCode};
+use std::env;
+use std::process;
+
+fn print_code(code: ResultCode) {
+    println!("{} ({:#x})", code, code);
+    println!(
+        "  module:      {} ({})",
+        code.module(),
+        code.module_name().unwrap_or("unknown")
+    );
+    println!("  description: {}", code.description());
+    if let Some(message) = code.known_message() {
+        println!(
+            "  message:     {}",
+            message.replace('\n', "\n               ")
+        );
+    }
+    if let Some(category) = code.category() {
+        println!("  range:       {}", category);
+    }
+    if let Some(url) = code.support_url() {
+        println!("  support:     {}", url);
+    }
+}
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.is_empty() {
+        eprintln!("usage: nx-errcode <2XXX-YYYY | 0xHEX | DECIMAL | game code>...");
+        process::exit(2);
+    }
+
+    let mut failed = false;
+    for arg in args {
+        if let Some(explanation) = game_error(&arg) {
+            println!("{}", arg);
+            println!("  game error:  {}", explanation);
+            continue;
+        }
+
+        match arg.parse() {
+            Ok(code) => print_code(code),
+            Err(err) => {
+                eprintln!("{}: {}", arg, err);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
@@ -0,0 +1,276 @@
+//! Horizon result codes and the tables for decoding them, generated from
+//! `assets/errcodes.py`. This is shared by libnx's `LibnxError` and by the
+//! `nx-errcode` tool, which runs on the host.
+
+use std::fmt;
+use std::num::{NonZeroU32, ParseIntError};
+use std::result::Result as StdResult;
+use std::str::FromStr;
+use thiserror::Error;
+
+include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
+
+fn find_range(ranges: &[(u32, u32, u32, &'static str)], error: u32) -> Option<&'static str> {
+    let code = ResultCode(error);
+    ranges
+        .iter()
+        .find(|&&(module, start, end, _)| {
+            code.module() == module && (start..=end).contains(&code.description())
+        })
+        .map(|&(_, _, _, message)| message)
+}
+
+fn error_to_string(error: u32) -> &'static str {
+    ERROR_CODES
+        .get(&error)
+        .copied()
+        .or_else(|| find_range(ERROR_RANGES, error))
+        .unwrap_or("Unknown error")
+}
+
+fn module_to_string(module: u32) -> Option<&'static str> {
+    MODULE_NAMES
+        .get(&module)
+        .copied()
+        .filter(|name| !name.is_empty())
+}
+
+fn extract_url(text: &str) -> Option<&str> {
+    let start = text.find("https://").or_else(|| text.find("http://"))?;
+    let url = &text[start..];
+    let end = url
+        .find(|c: char| c.is_whitespace() || c == ')')
+        .unwrap_or(url.len());
+    Some(&url[..end])
+}
+
+/// Looks up the explanation for a game-specific error code such as
+/// `2-AAB6A-3400`.
+pub fn game_error(code: &str) -> Option<&'static str> {
+    GAME_ERRORS.get(code.trim()).copied()
+}
+
+/// Finds the result codes in an error's `Debug` output, such as the message
+/// that `unwrap` and `expect` panic with. This looks for `ResultCode(..)`, and
+/// for `System(..)`, which is how libnx's `LibnxError` prints its code.
+pub fn find_codes(text: &str) -> impl Iterator<Item = ResultCode> + '_ {
+    text.match_indices('(').filter_map(move |(i, _)| {
+        let name_start = text[..i]
+            .trim_end_matches(|c: char| c.is_ascii_alphanumeric() || c == '_')
+            .len();
+        match &text[name_start..i] {
+            "ResultCode" | "System" => {}
+            _ => return None,
+        }
+        let rest = &text[i + 1..];
+        let end = rest.find(')')?;
+        rest[..end].parse().ok().map(ResultCode)
+    })
+}
+
+/// A raw Horizon result code, as returned by libnx and the kernel.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct ResultCode(pub u32);
+
+impl ResultCode {
+    pub const SUCCESS: Self = Self(0);
+
+    pub const fn new(module: u32, description: u32) -> Self {
+        Self((module & 0x1FF) | ((description & 0x1FFF) << 9))
+    }
+
+    pub const fn from_raw(raw: u32) -> Self {
+        Self(raw)
+    }
+
+    pub const fn raw(self) -> u32 {
+        self.0
+    }
+
+    pub const fn module(self) -> u32 {
+        self.0 & 0x1FF
+    }
+
+    pub const fn description(self) -> u32 {
+        (self.0 >> 9) & 0x1FFF
+    }
+
+    pub const fn is_success(self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn is_failure(self) -> bool {
+        !self.is_success()
+    }
+
+    /// The message for this code from `ERROR_CODES`, falling back to the
+    /// category from `ERROR_RANGES`, or "Unknown error".
+    pub fn message(self) -> &'static str {
+        error_to_string(self.0)
+    }
+
+    /// The message for exactly this code from `ERROR_CODES`, if known.
+    pub fn known_message(self) -> Option<&'static str> {
+        ERROR_CODES.get(&self.0).copied()
+    }
+
+    /// The category from `ERROR_RANGES` that this code falls in, if any.
+    pub fn category(self) -> Option<&'static str> {
+        find_range(ERROR_RANGES, self.0)
+    }
+
+    /// The name of the module that produced this code, from `MODULE_NAMES`.
+    pub fn module_name(self) -> Option<&'static str> {
+        module_to_string(self.module())
+    }
+
+    /// Decodes this code into a typed per-module error, for matching on.
+    pub fn kind(self) -> ResultKind {
+        self.into()
+    }
+
+    /// Nintendo's support text for this code, which usually ends with a link
+    /// to the relevant support page.
+    pub fn support_page(self) -> Option<&'static str> {
+        SUPPORT_PAGES.get(&*self.to_string()).copied()
+    }
+
+    /// A link to Nintendo's support page for this code or the range it falls
+    /// in.
+    pub fn support_url(self) -> Option<&'static str> {
+        self.support_page()
+            .and_then(extract_url)
+            .or_else(|| find_range(SUPPORT_RANGES, self.0))
+    }
+}
+
+impl From<u32> for ResultCode {
+    fn from(raw: u32) -> Self {
+        Self(raw)
+    }
+}
+
+impl From<ResultCode> for u32 {
+    fn from(code: ResultCode) -> Self {
+        code.0
+    }
+}
+
+impl From<NonZeroU32> for ResultCode {
+    fn from(raw: NonZeroU32) -> Self {
+        Self(raw.get())
+    }
+}
+
+/// Formats the code the way the Home menu does, i.e. `2XXX-YYYY`.
+impl fmt::Display for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:04}-{:04}", 2000 + self.module(), self.description())
+    }
+}
+
+#[derive(Error, Debug, Clone, PartialEq, Eq)]
+pub enum ParseResultCodeError {
+    #[error("invalid number in result code: {0}")]
+    Int(#[from] ParseIntError),
+    #[error("module {0} is out of range (expected 2000-2511)")]
+    Module(u32),
+    #[error("description {0} is out of range (expected 0-8191)")]
+    Description(u32),
+}
+
+/// Parses a display code (`2XXX-YYYY`), a hex value (`0x...`) or a decimal
+/// value.
+impl FromStr for ResultCode {
+    type Err = ParseResultCodeError;
+
+    fn from_str(s: &str) -> StdResult<Self, Self::Err> {
+        let s = s.trim();
+        let mut parts = s.splitn(2, '-');
+        if let (Some(module), Some(description)) = (parts.next(), parts.next()) {
+            let module: u32 = module.parse()?;
+            let description: u32 = description.parse()?;
+            if !(2000..2000 + 0x200).contains(&module) {
+                return Err(ParseResultCodeError::Module(module));
+            }
+            if description >= 0x2000 {
+                return Err(ParseResultCodeError::Description(description));
+            }
+            Ok(Self::new(module - 2000, description))
+        } else if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Ok(Self(u32::from_str_radix(hex, 16)?))
+        } else {
+            Ok(Self(s.parse()?))
+        }
+    }
+}
+
+impl fmt::LowerHex for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::LowerHex::fmt(&self.0, f)
+    }
+}
+
+impl fmt::UpperHex for ResultCode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::UpperHex::fmt(&self.0, f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::panic::{self, AssertUnwindSafe};
+
+    /// The same shape as libnx's `LibnxError`, whose derived `Debug` is what
+    /// ends up in `unwrap` messages.
+    #[allow(dead_code)]
+    #[derive(Debug)]
+    enum LibnxError {
+        System(NonZeroU32),
+        Context {
+            message: String,
+            source: Box<LibnxError>,
+            trace: Option<()>,
+        },
+    }
+
+    fn unwrap_message<E: fmt::Debug>(result: StdResult<(), E>) -> String {
+        let payload = panic::catch_unwind(AssertUnwindSafe(|| result.unwrap())).unwrap_err();
+        *payload.downcast::<String>().unwrap()
+    }
+
+    fn codes(text: &str) -> Vec<u32> {
+        find_codes(text).map(ResultCode::raw).collect()
+    }
+
+    #[test]
+    fn unwrapped_result_code() {
+        assert_eq!(codes(&unwrap_message(Err(ResultCode(0x202)))), [0x202]);
+    }
+
+    #[test]
+    fn unwrapped_libnx_error() {
+        let system = LibnxError::System(NonZeroU32::new(0x202).unwrap());
+        assert_eq!(codes(&unwrap_message(Err(system))), [0x202]);
+
+        let context = LibnxError::Context {
+            message: "opening save data (System(1))".to_string(),
+            source: Box::new(LibnxError::Context {
+                message: "mounting".to_string(),
+                source: Box::new(LibnxError::System(NonZeroU32::new(0x219a02).unwrap())),
+                trace: None,
+            }),
+            trace: Some(()),
+        };
+        // the message isn't a code, but there's no telling it apart
+        assert_eq!(codes(&unwrap_message(Err(context))), [1, 0x219a02]);
+    }
+
+    #[test]
+    fn other_names() {
+        assert!(codes("FileSystem(2) MyResultCode(3) System(x) System(").is_empty());
+        assert_eq!(codes("(ResultCode(4), ResultCode(5))"), [4, 5]);
+    }
+}
@@ -0,0 +1,411 @@
+//! A parser for the Python literals in `assets/errcodes.py`, used by
+//! nx-result's `build.rs`. It's its own crate so that its tests run on the host:
+//! `cargo test -p pylit --target x86_64-unknown-linux-gnu`.
+
+use std::fmt;
+
+/// A Python literal, as found in `assets/errcodes.py`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Int(i64),
+    Str(String),
+    List(Vec<Value>),
+    Dict(Vec<(Value, Value)>),
+}
+
+impl Value {
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Int(_) => "int",
+            Self::Str(_) => "str",
+            Self::List(_) => "list",
+            Self::Dict(_) => "dict",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.line, self.column, self.message)
+    }
+}
+
+pub type ParseResult<T> = Result<T, ParseError>;
+
+/// A recursive-descent parser for the subset of Python literal syntax used by
+/// `errcodes.py`: ints, strings, lists and dicts, with comments and trailing
+/// commas.
+pub struct Parser<'a> {
+    src: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    pub fn new(src: &'a str, pos: usize) -> Self {
+        Self { src, pos }
+    }
+
+    pub fn error<T>(&self, message: impl Into<String>) -> ParseResult<T> {
+        let before = &self.src[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.len() - before.rfind('\n').map_or(0, |i| i + 1) + 1;
+        Err(ParseError {
+            line,
+            column,
+            message: message.into(),
+        })
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.src[self.pos..]
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.rest().chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+            if trimmed.starts_with('#') {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                break;
+            }
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> ParseResult<()> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => self.error(format!("expected `{}`, found `{}`", expected, c)),
+            None => self.error(format!("expected `{}`, found end of file", expected)),
+        }
+    }
+
+    /// Consumes a `,` or `close`, returning whether the sequence has ended.
+    fn separator(&mut self, close: char) -> ParseResult<bool> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(',') => {
+                self.bump();
+                self.skip_whitespace();
+                if self.peek() == Some(close) {
+                    self.bump();
+                    Ok(true)
+                } else {
+                    Ok(false)
+                }
+            }
+            Some(c) if c == close => {
+                self.bump();
+                Ok(true)
+            }
+            Some(c) => self.error(format!("expected `,` or `{}`, found `{}`", close, c)),
+            None => self.error(format!("expected `,` or `{}`, found end of file", close)),
+        }
+    }
+
+    pub fn value(&mut self) -> ParseResult<Value> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.dict(),
+            Some('[') => self.list(),
+            Some('"') | Some('\'') => self.string(),
+            Some(c) if c == '-' || c.is_ascii_digit() => self.int(),
+            Some(c) => self.error(format!("expected a literal, found `{}`", c)),
+            None => self.error("expected a literal, found end of file"),
+        }
+    }
+
+    fn dict(&mut self) -> ParseResult<Value> {
+        self.expect('{')?;
+        let mut entries = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.bump();
+            return Ok(Value::Dict(entries));
+        }
+        loop {
+            let key = self.value()?;
+            self.expect(':')?;
+            let value = self.value()?;
+            entries.push((key, value));
+            if self.separator('}')? {
+                return Ok(Value::Dict(entries));
+            }
+        }
+    }
+
+    fn list(&mut self) -> ParseResult<Value> {
+        self.expect('[')?;
+        let mut items = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.bump();
+            return Ok(Value::List(items));
+        }
+        loop {
+            items.push(self.value()?);
+            if self.separator(']')? {
+                return Ok(Value::List(items));
+            }
+        }
+    }
+
+    fn int(&mut self) -> ParseResult<Value> {
+        let negative = self.peek() == Some('-');
+        if negative {
+            self.bump();
+        }
+        let rest = self.rest();
+        let (digits, radix) = if rest.starts_with("0x") || rest.starts_with("0X") {
+            (&rest[2..], 16)
+        } else {
+            (rest, 10)
+        };
+        let len = digits
+            .find(|c: char| !c.is_digit(radix) && c != '_')
+            .unwrap_or(digits.len());
+        let literal = digits[..len].replace('_', "");
+        if literal.is_empty() {
+            return self.error("expected digits");
+        }
+        let value = match i64::from_str_radix(&literal, radix) {
+            Ok(value) => value,
+            Err(err) => return self.error(format!("invalid integer `{}`: {}", literal, err)),
+        };
+        self.pos += rest.len() - digits.len() + len;
+        Ok(Value::Int(if negative { -value } else { value }))
+    }
+
+    /// Parses one or more adjacent string literals, concatenating them like
+    /// Python does.
+    fn string(&mut self) -> ParseResult<Value> {
+        let mut out = String::new();
+        loop {
+            self.string_part(&mut out)?;
+            let pos = self.pos;
+            self.skip_whitespace();
+            match self.peek() {
+                Some('"') | Some('\'') => {}
+                _ => {
+                    self.pos = pos;
+                    return Ok(Value::Str(out));
+                }
+            }
+        }
+    }
+
+    fn string_part(&mut self, out: &mut String) -> ParseResult<()> {
+        let quote = self.bump().unwrap();
+        loop {
+            match self.peek() {
+                Some('\n') | None => return self.error("unterminated string literal"),
+                Some(c) if c == quote => {
+                    self.bump();
+                    return Ok(());
+                }
+                Some('\\') => {
+                    self.bump();
+                    self.escape(out)?;
+                }
+                Some(c) => {
+                    self.bump();
+                    out.push(c);
+                }
+            }
+        }
+    }
+
+    fn escape(&mut self, out: &mut String) -> ParseResult<()> {
+        match self.bump() {
+            Some('\n') => {}
+            Some('\\') => out.push('\\'),
+            Some('\'') => out.push('\''),
+            Some('"') => out.push('"'),
+            Some('a') => out.push('\x07'),
+            Some('b') => out.push('\x08'),
+            Some('f') => out.push('\x0C'),
+            Some('n') => out.push('\n'),
+            Some('r') => out.push('\r'),
+            Some('t') => out.push('\t'),
+            Some('v') => out.push('\x0B'),
+            Some('x') => self.hex_escape(out, 2)?,
+            Some('u') => self.hex_escape(out, 4)?,
+            Some('U') => self.hex_escape(out, 8)?,
+            Some(c) if c.is_digit(8) => {
+                let mut value = c.to_digit(8).unwrap();
+                for _ in 0..2 {
+                    match self.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) => {
+                            value = value * 8 + digit;
+                            self.bump();
+                        }
+                        None => break,
+                    }
+                }
+                out.push(std::char::from_u32(value).unwrap());
+            }
+            // Python keeps unrecognised escapes verbatim.
+            Some(c) => {
+                out.push('\\');
+                out.push(c);
+            }
+            None => return self.error("unterminated string literal"),
+        }
+        Ok(())
+    }
+
+    fn hex_escape(&mut self, out: &mut String, len: usize) -> ParseResult<()> {
+        let digits = self.rest().get(..len).unwrap_or("");
+        let c = u32::from_str_radix(digits, 16)
+            .ok()
+            .filter(|_| digits.chars().all(|c| c.is_ascii_hexdigit()))
+            .and_then(std::char::from_u32);
+        match c {
+            Some(c) => {
+                self.pos += len;
+                out.push(c);
+                Ok(())
+            }
+            None => self.error(format!("invalid {}-digit escape sequence", len)),
+        }
+    }
+}
+
+/// Parses the literal assigned to the top-level variable `name`.
+pub fn parse_assignment(src: &str, name: &str) -> ParseResult<Value> {
+    let mut pos = 0;
+    for line in src.split('\n') {
+        if let Some(rest) = line.strip_prefix(name) {
+            let rest = rest.trim_start();
+            if let Some(rest) = rest.strip_prefix('=') {
+                let mut parser = Parser::new(src, pos + line.len() - rest.len());
+                let value = parser.value()?;
+                return Ok(value);
+            }
+        }
+        // and the newline
+        pos += line.len() + 1;
+    }
+    Parser::new(src, src.len()).error(format!("no assignment to `{}`", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(src: &str) -> ParseResult<Value> {
+        Parser::new(src, 0).value()
+    }
+
+    fn string(value: &str) -> Value {
+        Value::Str(value.to_string())
+    }
+
+    #[test]
+    fn escapes() {
+        assert_eq!(
+            parse(r#""a\nb\tc\\d\"e\x41é\101\q""#),
+            Ok(string("a\nb\tc\\d\"eAéA\\q"))
+        );
+        assert_eq!(parse("'line \\\ncontinued'"), Ok(string("line continued")));
+    }
+
+    #[test]
+    fn implicit_concatenation() {
+        assert_eq!(
+            parse("\"one \"  'two'\n    # comment\n    \" three\", 4"),
+            Ok(string("one two three"))
+        );
+    }
+
+    #[test]
+    fn apostrophes() {
+        assert_eq!(parse(r#""it's""#), Ok(string("it's")));
+        assert_eq!(parse(r"'it\'s'"), Ok(string("it's")));
+    }
+
+    #[test]
+    fn int_and_str_keys() {
+        assert_eq!(
+            parse("{0x202: 'FS', 2: \"two\", -1: 'minus', 1_000: 'k', '2002-0001': 'code'}"),
+            Ok(Value::Dict(vec![
+                (Value::Int(0x202), string("FS")),
+                (Value::Int(2), string("two")),
+                (Value::Int(-1), string("minus")),
+                (Value::Int(1000), string("k")),
+                (string("2002-0001"), string("code")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn trailing_commas() {
+        assert_eq!(
+            parse("{1: [2, 3,],}"),
+            Ok(Value::Dict(vec![(
+                Value::Int(1),
+                Value::List(vec![Value::Int(2), Value::Int(3)])
+            )]))
+        );
+        assert_eq!(parse("[]"), Ok(Value::List(vec![])));
+        assert_eq!(parse("{ }"), Ok(Value::Dict(vec![])));
+        assert!(parse("[,]").is_err());
+    }
+
+    #[test]
+    fn comments() {
+        assert_eq!(
+            parse("{ # opening\n  1: 'one', # after\n  # between\n  2: 'two # not a comment'\n}"),
+            Ok(Value::Dict(vec![
+                (Value::Int(1), string("one")),
+                (Value::Int(2), string("two # not a comment")),
+            ]))
+        );
+    }
+
+    #[test]
+    fn assignment() {
+        let src = "# header\nother = {1: 'x'}\ntable = {\n    2: 'y',\n}\n";
+        assert_eq!(
+            parse_assignment(src, "table"),
+            Ok(Value::Dict(vec![(Value::Int(2), string("y"))]))
+        );
+        assert_eq!(
+            parse_assignment(src, "missing").unwrap_err().message,
+            "no assignment to `missing`"
+        );
+    }
+
+    #[test]
+    fn error_location() {
+        let err = parse("{\n    1: 'one',\n    2 'two',\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (3, 7));
+        assert_eq!(err.to_string(), "3:7: expected `:`, found `'`");
+
+        let err = parse("{1: 'unterminated\n}").unwrap_err();
+        assert_eq!((err.line, err.column), (1, 18));
+        assert_eq!(err.message, "unterminated string literal");
+    }
+}
@@ -0,0 +1,188 @@
+//! An in-memory log of recent lines, for attaching to crash reports. It only
+//! uses std, so its tests run on the host:
+//! `cargo test -p ring-log --target x86_64-unknown-linux-gnu`.
+
+use std::fmt;
+use std::str;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::thread;
+
+/// The longest line a `RingLog` keeps. Longer lines are cut off.
+pub const RING_LINE_LEN: usize = 256;
+
+#[derive(Copy, Clone)]
+struct RingLine {
+    len: usize,
+    buf: [u8; RING_LINE_LEN],
+}
+
+struct Ring {
+    lines: Box<[RingLine]>,
+    /// The index the next line is written to.
+    next: usize,
+    len: usize,
+}
+
+/// Keeps the last few hundred lines in memory, so they can be attached to
+/// crash reports. All of the space is allocated up front, and reading never
+/// allocates, so it's safe to use from the panic hook.
+pub struct RingLog {
+    ring: Mutex<Ring>,
+    capacity: usize,
+}
+
+impl fmt::Debug for RingLog {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RingLog")
+            .field("capacity", &self.capacity)
+            .finish()
+    }
+}
+
+impl RingLog {
+    pub fn new(capacity: usize) -> Self {
+        let empty = RingLine {
+            len: 0,
+            buf: [0; RING_LINE_LEN],
+        };
+        Self {
+            ring: Mutex::new(Ring {
+                lines: vec![empty; capacity].into_boxed_slice(),
+                next: 0,
+                len: 0,
+            }),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Ring> {
+        self.ring
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Adds a line, replacing the oldest one if the log is full.
+    pub fn push(&self, line: &str) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut end = line.len().min(RING_LINE_LEN);
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+
+        let mut ring = self.lock();
+        let next = ring.next;
+        let slot = &mut ring.lines[next];
+        slot.buf[..end].copy_from_slice(&line.as_bytes()[..end]);
+        slot.len = end;
+        ring.next = (next + 1) % self.capacity;
+        ring.len = (ring.len + 1).min(self.capacity);
+    }
+
+    pub fn clear(&self) {
+        let mut ring = self.lock();
+        ring.next = 0;
+        ring.len = 0;
+    }
+
+    /// Calls `f` with each line, oldest first.
+    ///
+    /// This doesn't block, so that a panic while logging can't deadlock. If
+    /// the log stays locked by another thread (or by a panicking write on this
+    /// one), `f` isn't called and this returns `false`.
+    pub fn for_each(&self, mut f: impl FnMut(&str)) -> bool {
+        const ATTEMPTS: usize = 100;
+
+        let ring = (0..ATTEMPTS).find_map(|_| match self.ring.try_lock() {
+            Ok(ring) => Some(ring),
+            Err(TryLockError::Poisoned(poisoned)) => Some(poisoned.into_inner()),
+            Err(TryLockError::WouldBlock) => {
+                thread::yield_now();
+                None
+            }
+        });
+        let ring = match ring {
+            Some(ring) => ring,
+            None => return false,
+        };
+
+        let start = (ring.next + self.capacity - ring.len) % self.capacity.max(1);
+        for i in 0..ring.len {
+            let line = &ring.lines[(start + i) % self.capacity];
+            if let Ok(line) = str::from_utf8(&line.buf[..line.len]) {
+                f(line);
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(log: &RingLog) -> Vec<String> {
+        let mut lines = vec![];
+        assert!(log.for_each(|line| lines.push(line.to_string())));
+        lines
+    }
+
+    #[test]
+    fn wraps_oldest_first() {
+        let log = RingLog::new(3);
+        log.push("one");
+        log.push("two");
+        assert_eq!(lines(&log), ["one", "two"]);
+        log.push("three");
+        log.push("four");
+        log.push("five");
+        assert_eq!(lines(&log), ["three", "four", "five"]);
+    }
+
+    #[test]
+    fn truncates_on_char_boundary() {
+        let log = RingLog::new(2);
+        log.push(&"a".repeat(RING_LINE_LEN + 10));
+        // 'é' is two bytes, and would straddle the limit
+        log.push(&("a".repeat(RING_LINE_LEN - 1) + "é"));
+        let lines = lines(&log);
+        assert_eq!(lines[0], "a".repeat(RING_LINE_LEN));
+        assert_eq!(lines[1], "a".repeat(RING_LINE_LEN - 1));
+    }
+
+    #[test]
+    fn zero_capacity() {
+        let log = RingLog::new(0);
+        log.push("dropped");
+        assert_eq!(log.capacity(), 0);
+        assert!(lines(&log).is_empty());
+    }
+
+    #[test]
+    fn clear() {
+        let log = RingLog::new(2);
+        log.push("one");
+        log.push("two");
+        log.clear();
+        assert!(lines(&log).is_empty());
+        log.push("three");
+        assert_eq!(lines(&log), ["three"]);
+    }
+
+    #[test]
+    fn for_each_fails_while_locked() {
+        let log = RingLog::new(2);
+        log.push("one");
+        let ring = log.lock();
+        let mut called = false;
+        assert!(!log.for_each(|_| called = true));
+        assert!(!called);
+        drop(ring);
+        assert_eq!(lines(&log), ["one"]);
+    }
+}
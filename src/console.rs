@@ -1,11 +1,25 @@
+use crate::font;
+use crate::style::{Color, Style};
+use once_cell::sync::Lazy;
+use std::cell::RefCell;
 use std::ffi::c_void;
+use std::fmt;
+use std::io::{self, Write};
 use std::marker::{PhantomData, PhantomPinned};
+use std::panic::{self, AssertUnwindSafe};
 use std::pin::Pin;
 use std::ptr;
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
 
 static INITIALIZED: AtomicBool = AtomicBool::new(false);
 
+/// The address and generation of every live `PinnedConsole`, so that a
+/// `Selected` guard never reselects a console that has since been dropped,
+/// even if another one has been allocated at the same address.
+static LIVE: Lazy<Mutex<Vec<(usize, u64)>>> = Lazy::new(|| Mutex::new(vec![]));
+static NEXT_GENERATION: AtomicU64 = AtomicU64::new(0);
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
 #[repr(C)]
 pub struct ConsoleFont<'a> {
@@ -17,6 +31,82 @@ pub struct ConsoleFont<'a> {
     _phantom: PhantomData<&'a [u8]>,
 }
 
+impl<'a> ConsoleFont<'a> {
+    /// Wraps glyph tiles in the layout libnx's renderer expects: each glyph
+    /// is `tile_height` rows of `words_per_row(tile_width)` `u16`s, with the
+    /// leftmost pixel in the top bit.
+    ///
+    /// libnx's own renderer only draws 16x16 tiles. Other sizes need a
+    /// `ConsoleRender` such as `fb_render::FramebufferRender`, which can use
+    /// `pixel`.
+    ///
+    /// Fails if `gfx` is too short for the glyphs, or for what libnx's
+    /// renderer could read if the font ends up there anyway: 16 `u16`s for
+    /// each glyph up to and including `num_chars`, which its bounds check
+    /// lets through.
+    pub fn new(
+        gfx: &'a [u16],
+        ascii_offset: u16,
+        num_chars: u16,
+        tile_width: u16,
+        tile_height: u16,
+    ) -> Option<Self> {
+        if gfx.len() < Self::min_len(num_chars, tile_width, tile_height) {
+            return None;
+        }
+        Some(Self {
+            gfx: gfx.as_ptr() as *const c_void,
+            ascii_offset,
+            num_chars,
+            tile_width,
+            tile_height,
+            _phantom: PhantomData,
+        })
+    }
+
+    pub fn words_per_row(tile_width: u16) -> usize {
+        font::words_per_row(tile_width)
+    }
+
+    /// The shortest `gfx` that `new` accepts.
+    pub fn min_len(num_chars: u16, tile_width: u16, tile_height: u16) -> usize {
+        font::min_len(num_chars, tile_width, tile_height)
+    }
+
+    pub fn ascii_offset(&self) -> u16 {
+        self.ascii_offset
+    }
+
+    pub fn num_chars(&self) -> u16 {
+        self.num_chars
+    }
+
+    pub fn tile_width(&self) -> u16 {
+        self.tile_width
+    }
+
+    pub fn tile_height(&self) -> u16 {
+        self.tile_height
+    }
+
+    /// Whether a pixel of a glyph is set. Out of range pixels aren't.
+    pub fn pixel(&self, glyph: u32, x: u16, y: u16) -> bool {
+        if self.gfx.is_null()
+            || glyph >= u32::from(self.num_chars)
+            || x >= self.tile_width
+            || y >= self.tile_height
+        {
+            return false;
+        }
+        let words = Self::words_per_row(self.tile_width);
+        let row = glyph as usize * usize::from(self.tile_height) + usize::from(y);
+        let index = row * words + usize::from(x / 16);
+        // `new` checked the length, and libnx's fonts are laid out the same
+        let word = unsafe { *(self.gfx as *const u16).add(index) };
+        word & (0x8000 >> (x % 16)) != 0
+    }
+}
+
 impl Default for ConsoleFont<'_> {
     fn default() -> Self {
         Self {
@@ -40,6 +130,85 @@ pub struct ConsoleRenderer {
     pub flush_and_swap: unsafe extern "C" fn(con: *mut PrintConsole),
 }
 
+/// A safe way to customise how a console draws, attached with
+/// `Console::with_renderer`.
+///
+/// Positions are in characters, relative to the top left of the screen.
+/// Panics are caught, since they can't unwind into libnx.
+pub trait ConsoleRender {
+    /// Called by `Console::init`. Returning `false` fails initialization.
+    fn init(&mut self, console: &mut PrintConsole<'_>) -> bool;
+
+    fn deinit(&mut self, _console: &PrintConsole<'_>) {}
+
+    /// Draws the glyph at `glyph` in `console.font`, which has already had the
+    /// font's ASCII offset subtracted.
+    fn draw_char(&mut self, console: &PrintConsole<'_>, x: i32, y: i32, glyph: u32);
+
+    /// Scrolls the console's window up by one line.
+    fn scroll(&mut self, console: &PrintConsole<'_>);
+
+    /// Shows what has been drawn, called by `Console::update`.
+    fn flush(&mut self, console: &PrintConsole<'_>);
+}
+
+/// Finds the renderer used by the `PinnedConsole` containing `con`.
+///
+/// # Safety
+/// `con` must point to the console in a live `PinnedConsole`, which is only
+/// the case for consoles using `RUST_RENDERER`.
+unsafe fn with_renderer<T>(
+    con: *mut PrintConsole,
+    default: T,
+    f: impl FnOnce(&mut dyn ConsoleRender, &mut PrintConsole) -> T,
+) -> T {
+    let pinned = con as *mut PinnedConsole;
+    let console = &mut (*pinned).console;
+    let renderer = match &(*pinned).renderer {
+        Renderer::None => return default,
+        Renderer::Owned(renderer) => &**renderer,
+        // windows can't outlive the console they share a renderer with
+        Renderer::Shared(renderer) => &**renderer,
+    };
+    // a renderer that prints would draw into itself
+    let mut renderer = match renderer.try_borrow_mut() {
+        Ok(renderer) => renderer,
+        Err(_) => return default,
+    };
+    panic::catch_unwind(AssertUnwindSafe(|| f(&mut *renderer, console))).unwrap_or(default)
+}
+
+unsafe extern "C" fn render_init(con: *mut PrintConsole) -> bool {
+    with_renderer(con, false, |renderer, console| renderer.init(console))
+}
+
+unsafe extern "C" fn render_deinit(con: *mut PrintConsole) {
+    with_renderer(con, (), |renderer, console| renderer.deinit(console))
+}
+
+unsafe extern "C" fn render_draw_char(con: *mut PrintConsole, x: i32, y: i32, c: i32) {
+    with_renderer(con, (), |renderer, console| {
+        renderer.draw_char(console, x, y, c as u32)
+    })
+}
+
+unsafe extern "C" fn render_scroll(con: *mut PrintConsole) {
+    with_renderer(con, (), |renderer, console| renderer.scroll(console))
+}
+
+unsafe extern "C" fn render_flush(con: *mut PrintConsole) {
+    with_renderer(con, (), |renderer, console| renderer.flush(console))
+}
+
+/// Dispatches to the `ConsoleRender` of the `PinnedConsole` being drawn.
+static RUST_RENDERER: ConsoleRenderer = ConsoleRenderer {
+    init: render_init,
+    deinit: render_deinit,
+    draw_char: render_draw_char,
+    scroll_window: render_scroll,
+    flush_and_swap: render_flush,
+};
+
 #[derive(Copy, Clone)]
 #[repr(C)]
 pub struct PrintConsole<'a> {
@@ -62,9 +231,22 @@ pub struct PrintConsole<'a> {
     pub initialized: bool,
 }
 
-#[derive(Copy, Clone)]
-#[repr(transparent)]
-struct PinnedConsole<'a>(pub PrintConsole<'a>, pub PhantomPinned);
+// the console must come first, since renderers find the rest of the struct
+// from a pointer to it
+#[repr(C)]
+struct PinnedConsole<'a> {
+    console: PrintConsole<'a>,
+    renderer: Renderer<'a>,
+    _pin: PhantomPinned,
+}
+
+enum Renderer<'a> {
+    /// The console's `renderer` is either libnx's or a raw `ConsoleRenderer`.
+    None,
+    Owned(Box<RefCell<dyn ConsoleRender + 'a>>),
+    /// Borrowed from the console a window was made from.
+    Shared(*const RefCell<dyn ConsoleRender + 'a>),
+}
 
 extern "C" {
     fn consoleGetDefault() -> *mut PrintConsole<'static>;
@@ -72,9 +254,47 @@ extern "C" {
     fn consoleInit<'a>(console: *mut PrintConsole<'a>) -> *mut PrintConsole<'a>;
     fn consoleExit<'a>(console: *mut PrintConsole<'a>);
     fn consoleUpdate<'a>(console: *mut PrintConsole<'a>);
+    fn consoleSetWindow<'a>(
+        console: *mut PrintConsole<'a>,
+        x: i32,
+        y: i32,
+        width: i32,
+        height: i32,
+    );
 }
 
-pub struct Console<'a>(Option<Pin<Box<PinnedConsole<'a>>>>);
+/// A libnx console. Each one other than the default is its own allocation, so
+/// several can share the screen through their windows.
+pub struct Console<'a> {
+    inner: Option<Pin<Box<PinnedConsole<'a>>>>,
+    auto_update: bool,
+    /// Whether dropping this exits the console, which windows don't, since
+    /// they share their renderer.
+    exit_on_drop: bool,
+}
+
+/// Reselects the previously selected console when dropped, or the default
+/// console if that has been dropped since.
+pub struct Selected<'c> {
+    previous: *mut PrintConsole<'static>,
+    /// The previous console's generation, or `None` if it wasn't one of ours.
+    generation: Option<u64>,
+    _console: PhantomData<&'c mut ()>,
+}
+
+impl Drop for Selected<'_> {
+    fn drop(&mut self) {
+        let live = LIVE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let address = self.previous as usize;
+        let previous = match self.generation {
+            Some(generation) if live.contains(&(address, generation)) => self.previous,
+            _ => unsafe { consoleGetDefault() },
+        };
+        unsafe {
+            consoleSelect(previous);
+        }
+    }
+}
 
 impl Console<'static> {
     pub fn new() -> Self {
@@ -90,19 +310,78 @@ impl Console<'static> {
             }
         }
 
-        let mut console = Self(None);
+        let mut console = Self {
+            inner: None,
+            auto_update: false,
+            exit_on_drop: false,
+        };
         console.init();
         console
     }
 }
 
 impl<'a> Console<'a> {
+    fn pinned(console: PrintConsole<'a>, renderer: Renderer<'a>, exit_on_drop: bool) -> Self {
+        let pinned = Box::pin(PinnedConsole {
+            console,
+            renderer,
+            _pin: PhantomPinned,
+        });
+        let address = &*pinned as *const PinnedConsole as usize;
+        let generation = NEXT_GENERATION.fetch_add(1, Ordering::Relaxed);
+        LIVE.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push((address, generation));
+        Self {
+            inner: Some(pinned),
+            auto_update: false,
+            exit_on_drop,
+        }
+    }
+
     pub fn new_with(options: PrintConsole<'a>) -> Self {
-        Self(Some(Box::pin(PinnedConsole(options, PhantomPinned))))
+        Self::pinned(options, Renderer::None, true)
+    }
+
+    /// Creates a console that draws with `renderer`, replacing
+    /// `options.renderer`.
+    pub fn with_renderer(mut options: PrintConsole<'a>, renderer: impl ConsoleRender + 'a) -> Self {
+        options.renderer = Some(&RUST_RENDERER);
+        Self::pinned(
+            options,
+            Renderer::Owned(Box::new(RefCell::new(renderer))),
+            true,
+        )
+    }
+
+    /// Creates a console that prints to part of the screen, sharing this
+    /// console's renderer, font and colours. Positions and sizes are in
+    /// characters.
+    ///
+    /// This console should already be initialized. The window is never
+    /// initialized or exited itself, so it can't outlive this console.
+    pub fn window(&self, x: i32, y: i32, width: i32, height: i32) -> Console<'_> {
+        let (console, renderer) = match &self.inner {
+            Some(pinned) => {
+                let renderer = match &pinned.renderer {
+                    Renderer::None => Renderer::None,
+                    Renderer::Owned(renderer) => Renderer::Shared(&**renderer),
+                    Renderer::Shared(renderer) => Renderer::Shared(*renderer),
+                };
+                (pinned.console, renderer)
+            }
+            None => (unsafe { ptr::read(consoleGetDefault()) }, Renderer::None),
+        };
+
+        let mut window = Console::pinned(console, renderer, false);
+        unsafe {
+            consoleSetWindow(window.as_raw(), x, y, width, height);
+        }
+        window
     }
 
     pub fn as_raw(&mut self) -> *mut PrintConsole<'a> {
-        if let Some(ref mut pin) = &mut self.0 {
+        if let Some(ref mut pin) = &mut self.inner {
             unsafe { Pin::get_unchecked_mut(Pin::as_mut(pin)) as *mut _ as *mut _ }
         } else {
             unsafe { consoleGetDefault() as *mut c_void as *mut PrintConsole<'a> }
@@ -121,24 +400,128 @@ impl<'a> Console<'a> {
         }
     }
 
-    pub fn select(&mut self) {
-        unsafe {
-            consoleSelect(self.as_raw());
+    /// Selects this console for stdout until the guard is dropped.
+    pub fn select(&mut self) -> Selected<'_> {
+        // hold the lock so the previous console can't be dropped in between
+        let live = LIVE.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = unsafe { consoleSelect(self.as_raw()) }.cast();
+        let generation = live
+            .iter()
+            .find(|&&(address, _)| address == previous as usize)
+            .map(|&(_, generation)| generation);
+        Selected {
+            previous,
+            generation,
+            _console: PhantomData,
         }
     }
+
+    pub fn set_fg(&mut self, color: Color) {
+        unsafe { (*self.as_raw()).set_fg(color) }
+    }
+
+    pub fn set_bg(&mut self, color: Color) {
+        unsafe { (*self.as_raw()).set_bg(color) }
+    }
+
+    pub fn set_style(&mut self, style: Style) {
+        unsafe { (*self.as_raw()).set_style(style) }
+    }
+
+    /// Whether writes call `update` when they're done, so they show up
+    /// without waiting for the next frame. Defaults to `false`.
+    pub fn set_auto_update(&mut self, auto_update: bool) {
+        self.auto_update = auto_update;
+    }
+}
+
+/// Prints to this console through stdout, selecting it for the duration.
+impl Write for Console<'_> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let stdout = io::stdout();
+        let mut stdout = stdout.lock();
+        // anything still buffered belongs to the previously selected console
+        stdout.flush()?;
+        {
+            let _selected = self.select();
+            stdout.write_all(buf)?;
+            stdout.flush()?;
+        }
+        if self.auto_update {
+            self.update();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stdout().flush()?;
+        if self.auto_update {
+            self.update();
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Write for Console<'_> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.write_all(s.as_bytes()).map_err(|_| fmt::Error)
+    }
 }
 
 impl Drop for Console<'_> {
     fn drop(&mut self) {
+        // the default console is exited at exit
+        if self.inner.is_none() {
+            return;
+        }
+        let raw = self.as_raw();
+        LIVE.lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .retain(|&(address, _)| address != raw as usize);
+
         unsafe {
-            if self.0.is_some() {
-                consoleExit(Console::new().as_raw()); // switch to a 'static console and get rid of it (to prevent current console being freed)
-                consoleExit(self.as_raw());
+            // don't leave libnx printing to freed memory, but don't
+            // initialize anything either
+            let default = consoleGetDefault();
+            let current = consoleSelect(default);
+            if !ptr::eq(current, raw) {
+                consoleSelect(current);
+            }
+            if self.exit_on_drop {
+                consoleExit(raw);
             }
         }
     }
 }
 
+impl PrintConsole<'_> {
+    /// The foreground colour, if it's one of the eight standard colours.
+    pub fn fg_color(&self) -> Option<Color> {
+        Color::from_index(self.fg)
+    }
+
+    pub fn set_fg(&mut self, color: Color) {
+        self.fg = color.index();
+    }
+
+    pub fn bg_color(&self) -> Option<Color> {
+        Color::from_index(self.bg)
+    }
+
+    pub fn set_bg(&mut self, color: Color) {
+        self.bg = color.index();
+    }
+
+    pub fn style(&self) -> Style {
+        Style::from_flags(self.flags)
+    }
+
+    /// Replaces the style, keeping any flags that aren't styles.
+    pub fn set_style(&mut self, style: Style) {
+        self.flags = (self.flags & !Style::from_flags(!0).flags()) | style.flags();
+    }
+}
+
 impl Default for PrintConsole<'static> {
     fn default() -> Self {
         unsafe { ptr::read(consoleGetDefault()) }
@@ -0,0 +1,330 @@
+//! Crash reports, written to the SD card as one JSON file per panic.
+
+use crate::result::{find_codes, LibnxError, ResultCode};
+use crate::trace::Frame;
+use std::any::Any;
+use std::fmt::Write as _;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::panic::PanicInfo;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const DEFAULT_DIR: &str = "sdmc:/libnx-rs/crash-reports";
+pub const DEFAULT_KEEP: usize = 10;
+
+/// Bumped whenever a field is removed or changes meaning.
+pub const FORMAT_VERSION: u32 = 1;
+
+const PREFIX: &str = "crash-";
+const EXTENSION: &str = "json";
+
+/// Where crash reports are written, and how many are kept.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CrashReports {
+    dir: PathBuf,
+    keep: usize,
+}
+
+impl Default for CrashReports {
+    fn default() -> Self {
+        Self {
+            dir: DEFAULT_DIR.into(),
+            keep: DEFAULT_KEEP,
+        }
+    }
+}
+
+impl CrashReports {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            ..Self::default()
+        }
+    }
+
+    /// How many reports to keep, including the newest. Older reports are
+    /// deleted after each write. At least one is always kept.
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = keep.max(1);
+        self
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes `report` to a new file, then deletes the oldest reports.
+    pub fn write(&self, report: &Report) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.dir)?;
+
+        let mut timestamp = report.timestamp;
+        let (path, mut file) = loop {
+            let path = self
+                .dir
+                .join(format!("{}{:013}.{}", PREFIX, timestamp, EXTENSION));
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(file) => break (path, file),
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => timestamp += 1,
+                Err(err) => return Err(err),
+            }
+        };
+        file.write_all(report.to_json().as_bytes())?;
+        drop(file);
+
+        self.rotate()?;
+        Ok(path)
+    }
+
+    fn rotate(&self) -> io::Result<()> {
+        let mut reports = fs::read_dir(&self.dir)?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| entry.path())
+            .filter(|path| is_report(path))
+            .collect::<Vec<_>>();
+        // the names are zero-padded timestamps, so they sort oldest first
+        reports.sort();
+
+        let excess = reports.len().saturating_sub(self.keep);
+        for path in &reports[..excess] {
+            let _ = fs::remove_file(path);
+        }
+        Ok(())
+    }
+}
+
+fn is_report(path: &Path) -> bool {
+    let name = path.file_name().and_then(|name| name.to_str());
+    let extension = path.extension().and_then(|extension| extension.to_str());
+    match (name, extension) {
+        (Some(name), Some(extension)) => name.starts_with(PREFIX) && extension == EXTENSION,
+        _ => false,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Location {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// A result code, along with the context messages wrapping it, outermost
+/// first.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ResultChain {
+    pub context: Vec<String>,
+    pub code: ResultCode,
+}
+
+impl From<&LibnxError> for ResultChain {
+    fn from(mut error: &LibnxError) -> Self {
+        let mut context = vec![];
+        while let LibnxError::Context {
+            message, source, ..
+        } = error
+        {
+            context.push(message.clone());
+            error = source;
+        }
+        Self {
+            context,
+            code: error.code(),
+        }
+    }
+}
+
+impl From<ResultCode> for ResultChain {
+    fn from(code: ResultCode) -> Self {
+        Self {
+            context: vec![],
+            code,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Report {
+    /// Milliseconds since the Unix epoch, or 0 if the clock isn't available.
+    pub timestamp: u64,
+    pub thread: String,
+    pub payload: String,
+    pub location: Option<Location>,
+    pub results: Vec<ResultChain>,
+    pub backtrace: Vec<Frame>,
+    /// Recent log lines, oldest first.
+    pub log: Vec<String>,
+}
+
+impl Report {
+    pub fn new(info: &PanicInfo, backtrace: Vec<Frame>) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|since| since.as_millis() as u64)
+            .unwrap_or(0);
+        let thread = thread::current().name().unwrap_or("<unnamed>").to_string();
+        let payload = payload_message(info.payload());
+        let location = info.location().map(|location| Location {
+            file: location.file().to_string(),
+            line: location.line(),
+            column: location.column(),
+        });
+        let results = payload_results(info.payload(), &payload);
+
+        Self {
+            timestamp,
+            thread,
+            payload,
+            location,
+            results,
+            backtrace,
+            log: vec![],
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        let _ = write!(
+            out,
+            "{{\"version\":{},\"timestamp\":{},\"thread\":",
+            FORMAT_VERSION, self.timestamp
+        );
+        json_string(&mut out, &self.thread);
+        out += ",\"payload\":";
+        json_string(&mut out, &self.payload);
+
+        out += ",\"location\":";
+        match &self.location {
+            Some(location) => {
+                out += "{\"file\":";
+                json_string(&mut out, &location.file);
+                let _ = write!(
+                    out,
+                    ",\"line\":{},\"column\":{}}}",
+                    location.line, location.column
+                );
+            }
+            None => out += "null",
+        }
+
+        out += ",\"results\":[";
+        for (i, chain) in self.results.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let code = chain.code;
+            out += "{\"code\":";
+            json_string(&mut out, &code.to_string());
+            let _ = write!(
+                out,
+                ",\"raw\":{},\"module\":{},\"description\":{},\"message\":",
+                code.raw(),
+                code.module(),
+                code.description()
+            );
+            json_string(&mut out, code.message());
+            out += ",\"context\":[";
+            for (i, message) in chain.context.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                json_string(&mut out, message);
+            }
+            out += "]}";
+        }
+
+        out += "],\"backtrace\":[";
+        for (i, frame) in self.backtrace.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            let _ = write!(out, "{{\"ip\":\"{:#x}\",\"symbol\":", frame.ip);
+            json_option(&mut out, frame.symbol.as_deref());
+            out += ",\"file\":";
+            json_option(&mut out, frame.file.as_deref());
+            out += ",\"line\":";
+            match frame.line {
+                Some(line) => {
+                    let _ = write!(out, "{}", line);
+                }
+                None => out += "null",
+            }
+            out.push('}');
+        }
+
+        out += "],\"log\":[";
+        for (i, line) in self.log.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            json_string(&mut out, line);
+        }
+        out += "]}\n";
+        out
+    }
+}
+
+/// The panic message, or a description of the payload if it isn't a string.
+pub fn payload_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else if let Some(error) = payload.downcast_ref::<LibnxError>() {
+        error.chain()
+    } else if let Some(code) = payload.downcast_ref::<ResultCode>() {
+        code.to_string()
+    } else {
+        "Box<Any>".to_string()
+    }
+}
+
+/// Finds the result codes behind a panic.
+///
+/// Panicking with a `LibnxError` or `ResultCode` as the payload (such as with
+/// `panic!(error)`) keeps the full chain. Otherwise, this falls back to
+/// finding the codes in the `Debug` output that `unwrap` and `expect` leave
+/// in the message, but that loses any context.
+fn payload_results(payload: &(dyn Any + Send), message: &str) -> Vec<ResultChain> {
+    if let Some(error) = payload.downcast_ref::<LibnxError>() {
+        return vec![error.into()];
+    }
+    if let Some(code) = payload.downcast_ref::<ResultCode>() {
+        return vec![(*code).into()];
+    }
+    if let Some(error) = payload
+        .downcast_ref::<io::Error>()
+        .and_then(|error| error.get_ref())
+        .and_then(|error| error.downcast_ref::<LibnxError>())
+    {
+        return vec![error.into()];
+    }
+
+    find_codes(message).map(ResultChain::from).collect()
+}
+
+fn json_option(out: &mut String, value: Option<&str>) {
+    match value {
+        Some(value) => json_string(out, value),
+        None => *out += "null",
+    }
+}
+
+fn json_string(out: &mut String, value: &str) {
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => *out += "\\\"",
+            '\\' => *out += "\\\\",
+            '\n' => *out += "\\n",
+            '\r' => *out += "\\r",
+            '\t' => *out += "\\t",
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{:04x}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
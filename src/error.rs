@@ -1,5 +1,80 @@
-use std::mem::MaybeUninit;
+use crate::result::{IntoResult, LibnxError, ResultCode};
+use crate::settings::{Language, Region};
+use crate::trace;
+use crate::Result;
+use std::fmt;
+use std::mem::{self, MaybeUninit};
 use std::os::raw::c_char;
+use std::result::Result as StdResult;
+use std::slice;
+use thiserror::Error;
+
+const ELLIPSIS: &str = "\u{2026}";
+
+/// The longest dialog or fullscreen message that fits, leaving room for the
+/// NUL terminator.
+pub const MAX_MESSAGE_LEN: usize = 0x800 - 1;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum MessageField {
+    Dialog,
+    Fullscreen,
+}
+
+impl fmt::Display for MessageField {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Dialog => f.write_str("dialog"),
+            Self::Fullscreen => f.write_str("fullscreen"),
+        }
+    }
+}
+
+#[derive(Error, Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[error("{field} message is {len} bytes, but at most {max} fit")]
+pub struct MessageTooLong {
+    pub field: MessageField,
+    pub len: usize,
+    pub max: usize,
+}
+
+/// Copies `message` into `buf` with a NUL terminator, failing if it doesn't
+/// fit.
+fn copy_message(
+    buf: &mut [c_char],
+    message: &str,
+    field: MessageField,
+) -> StdResult<(), MessageTooLong> {
+    let max = buf.len() - 1;
+    if message.len() > max {
+        return Err(MessageTooLong {
+            field,
+            len: message.len(),
+            max,
+        });
+    }
+    for (dst, &src) in buf.iter_mut().zip(message.as_bytes()) {
+        *dst = src as c_char;
+    }
+    buf[message.len()] = 0;
+    Ok(())
+}
+
+/// Copies `message` into `buf` with a NUL terminator, cutting it off at a
+/// character boundary and appending an ellipsis if it doesn't fit.
+fn copy_message_truncated(buf: &mut [c_char], message: &str, field: MessageField) {
+    let max = buf.len() - 1;
+    if message.len() <= max {
+        copy_message(buf, message, field).unwrap();
+        return;
+    }
+    let mut end = max - ELLIPSIS.len();
+    while !message.is_char_boundary(end) {
+        end -= 1;
+    }
+    let truncated = [&message[..end], ELLIPSIS].concat();
+    copy_message(buf, &truncated, field).unwrap();
+}
 
 #[repr(C)]
 pub struct ErrorCommonHeader {
@@ -11,6 +86,100 @@ pub struct ErrorCommonHeader {
     pub context_flag_2: u8,
 }
 
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum ErrorType {
+    Normal = 0,
+    System = 1,
+    Application = 2,
+    Eula = 3,
+    Pctl = 4,
+    Record = 5,
+    SystemUpdateEula = 8,
+}
+
+impl ErrorCommonHeader {
+    pub fn new(typ: ErrorType) -> Self {
+        Self {
+            typ: typ as u8,
+            jump_flag: 1,
+            unknown: [0; 3],
+            context_flag: 0,
+            result_flag: 0,
+            context_flag_2: 0,
+        }
+    }
+}
+
+/// The `2XXX-YYYY` form of a result code, as the applet expects it.
+#[derive(Default, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(C)]
+pub struct ErrorCode {
+    pub category: u32,
+    pub number: u32,
+}
+
+impl From<ResultCode> for ErrorCode {
+    fn from(code: ResultCode) -> Self {
+        Self {
+            category: 2000 + code.module(),
+            number: code.description(),
+        }
+    }
+}
+
+#[repr(C)]
+pub struct ErrorCommonArg {
+    pub hdr: ErrorCommonHeader,
+    pub error_code: ErrorCode,
+    pub result: u32,
+}
+
+#[repr(C)]
+pub struct ErrorSystemArg {
+    pub hdr: ErrorCommonHeader,
+    pub error_code: ErrorCode,
+    pub language_code: u64,
+    pub dialog_message: [c_char; 0x800],
+    pub fullscreen_message: [c_char; 0x800],
+}
+
+#[repr(C)]
+pub struct ErrorContext {
+    pub data: [u8; 0x200],
+}
+
+#[repr(C)]
+pub struct ErrorSystemConfig {
+    pub arg: ErrorSystemArg,
+    pub ctx: ErrorContext,
+}
+
+impl Default for ErrorSystemConfig {
+    fn default() -> Self {
+        unsafe { MaybeUninit::zeroed().assume_init() }
+    }
+}
+
+#[repr(C)]
+pub struct ErrorResultBacktrace {
+    pub count: i32,
+    pub backtrace: [u32; 0x20],
+}
+
+#[repr(C)]
+pub struct ErrorEulaArg {
+    pub hdr: ErrorCommonHeader,
+    pub region_code: u32,
+}
+
+#[repr(C)]
+pub struct ErrorRecordArg {
+    pub hdr: ErrorCommonHeader,
+    pub error_code: ErrorCode,
+    pub timestamp: u64,
+}
+
 #[repr(C, packed)]
 pub struct ErrorApplicationArg {
     pub hdr: ErrorCommonHeader,
@@ -32,28 +201,332 @@ impl Default for ErrorApplicationConfig {
 }
 
 impl ErrorApplicationConfig {
-    pub fn new(dialog_message: &str, fullscreen_message: Option<&str>) -> Option<Self> {
+    fn with_language() -> Self {
         let mut cfg = Self::default();
-        cfg.arg.hdr.typ = 2;
-        cfg.arg.hdr.jump_flag = 1;
-        cfg.arg
-            .dialog_message
-            .get_mut(..dialog_message.len())?
-            .copy_from_slice(dialog_message.as_bytes());
+        cfg.arg.hdr = ErrorCommonHeader::new(ErrorType::Application);
+        cfg.arg.language_code = Language::system().unwrap_or_default().code();
+        cfg
+    }
+
+    /// Creates a config, truncating messages that are too long for the applet.
+    pub fn new(dialog_message: &str, fullscreen_message: Option<&str>) -> Self {
+        let mut cfg = Self::with_language();
+        copy_message_truncated(
+            &mut cfg.arg.dialog_message,
+            dialog_message,
+            MessageField::Dialog,
+        );
+        if let Some(fullscreen_message) = fullscreen_message {
+            copy_message_truncated(
+                &mut cfg.arg.fullscreen_message,
+                fullscreen_message,
+                MessageField::Fullscreen,
+            );
+        }
+        cfg
+    }
+
+    /// Creates a config, failing if either message is too long for the applet.
+    pub fn try_new(
+        dialog_message: &str,
+        fullscreen_message: Option<&str>,
+    ) -> StdResult<Self, MessageTooLong> {
+        let mut cfg = Self::with_language();
+        copy_message(
+            &mut cfg.arg.dialog_message,
+            dialog_message,
+            MessageField::Dialog,
+        )?;
         if let Some(fullscreen_message) = fullscreen_message {
-            cfg.arg
-                .fullscreen_message
-                .get_mut(..fullscreen_message.len())?
-                .copy_from_slice(fullscreen_message.as_bytes());
+            copy_message(
+                &mut cfg.arg.fullscreen_message,
+                fullscreen_message,
+                MessageField::Fullscreen,
+            )?;
         }
-        // TODO: language code
-        Some(cfg)
+        Ok(cfg)
+    }
+
+    pub fn language(mut self, language: Language) -> Self {
+        self.arg.language_code = language.code();
+        self
     }
 
-    pub fn show(&self) -> u32 {
+    pub fn show(&self) -> Result<()> {
         extern "C" {
             fn errorApplicationShow(c: *const ErrorApplicationConfig) -> u32;
         }
-        unsafe { errorApplicationShow(self as *const _) }
+        unsafe { errorApplicationShow(self as *const _).into_result() }
+    }
+}
+
+fn as_bytes<T>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, mem::size_of::<T>()) }
+}
+
+/// Launches the error applet with `arg` and an optional second storage,
+/// waiting for it to exit.
+fn launch(arg: &[u8], extra: Option<&[u8]>) -> Result<()> {
+    let arg_ptr = arg.as_ptr();
+    let arg_len = arg.len();
+    let extra = extra.unwrap_or(&[]);
+    let extra_ptr = extra.as_ptr();
+    let extra_len = extra.len();
+
+    let rc = unsafe {
+        cpp!([arg_ptr as "const void *", arg_len as "size_t", extra_ptr as "const void *", extra_len as "size_t"] -> u32 as "Result" {
+            AppletHolder holder;
+            LibAppletArgs commonargs;
+
+            Result rc = appletCreateLibraryApplet(&holder, AppletId_LibraryAppletError, LibAppletMode_AllForeground);
+            if (R_FAILED(rc)) return rc;
+
+            libappletArgsCreate(&commonargs, 1);
+            rc = libappletArgsPush(&commonargs, &holder);
+            if (R_SUCCEEDED(rc)) rc = libappletPushInData(&holder, arg_ptr, arg_len);
+            if (R_SUCCEEDED(rc) && extra_len != 0) rc = libappletPushInData(&holder, extra_ptr, extra_len);
+            if (R_SUCCEEDED(rc)) rc = appletHolderStart(&holder);
+            if (R_SUCCEEDED(rc)) appletHolderJoin(&holder);
+
+            appletHolderClose(&holder);
+            return rc;
+        })
+    };
+
+    rc.into_result()
+}
+
+/// What the error applet should display.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum ErrorMode<'a> {
+    /// A result code with the system-provided text for it.
+    Result(ResultCode),
+    /// A result code with custom dialog text.
+    System {
+        code: ResultCode,
+        dialog_message: &'a str,
+        fullscreen_message: Option<&'a str>,
+    },
+    /// Custom dialog text without a result code.
+    Application {
+        dialog_message: &'a str,
+        fullscreen_message: Option<&'a str>,
+    },
+    /// A result code along with the codes that led to it. Only the first 32
+    /// are shown.
+    ResultBacktrace {
+        result: ResultCode,
+        backtrace: &'a [ResultCode],
+    },
+    Eula(Region),
+    SystemUpdateEula(Region),
+    /// Records a result code in the error history without showing it.
+    Record {
+        code: ResultCode,
+        timestamp: u64,
+    },
+}
+
+/// Builds and shows any of the error applet's modes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct ErrorApplet<'a> {
+    mode: ErrorMode<'a>,
+    jump: bool,
+    language: Option<Language>,
+}
+
+impl<'a> ErrorApplet<'a> {
+    pub fn new(mode: ErrorMode<'a>) -> Self {
+        Self {
+            mode,
+            jump: true,
+            language: None,
+        }
+    }
+
+    pub fn result(code: ResultCode) -> Self {
+        Self::new(ErrorMode::Result(code))
+    }
+
+    pub fn system(
+        code: ResultCode,
+        dialog_message: &'a str,
+        fullscreen_message: Option<&'a str>,
+    ) -> Self {
+        Self::new(ErrorMode::System {
+            code,
+            dialog_message,
+            fullscreen_message,
+        })
+    }
+
+    pub fn application(dialog_message: &'a str, fullscreen_message: Option<&'a str>) -> Self {
+        Self::new(ErrorMode::Application {
+            dialog_message,
+            fullscreen_message,
+        })
+    }
+
+    pub fn result_backtrace(result: ResultCode, backtrace: &'a [ResultCode]) -> Self {
+        Self::new(ErrorMode::ResultBacktrace { result, backtrace })
+    }
+
+    pub fn eula(region: Region) -> Self {
+        Self::new(ErrorMode::Eula(region))
+    }
+
+    pub fn system_update_eula(region: Region) -> Self {
+        Self::new(ErrorMode::SystemUpdateEula(region))
+    }
+
+    pub fn record(code: ResultCode, timestamp: u64) -> Self {
+        Self::new(ErrorMode::Record { code, timestamp })
+    }
+
+    /// Whether the applet offers to jump to the support page. Defaults to
+    /// `true`.
+    pub fn jump(mut self, jump: bool) -> Self {
+        self.jump = jump;
+        self
+    }
+
+    /// The language for custom text. Defaults to the system language.
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    pub fn mode(&self) -> ErrorMode<'a> {
+        self.mode
+    }
+
+    pub fn header(&self) -> ErrorCommonHeader {
+        let typ = match self.mode {
+            ErrorMode::Result(_) | ErrorMode::ResultBacktrace { .. } => ErrorType::Normal,
+            ErrorMode::System { .. } => ErrorType::System,
+            ErrorMode::Application { .. } => ErrorType::Application,
+            ErrorMode::Eula(_) => ErrorType::Eula,
+            ErrorMode::SystemUpdateEula(_) => ErrorType::SystemUpdateEula,
+            ErrorMode::Record { .. } => ErrorType::Record,
+        };
+        let mut hdr = ErrorCommonHeader::new(typ);
+        hdr.jump_flag = self.jump as u8;
+        if let ErrorMode::Result(_) | ErrorMode::ResultBacktrace { .. } = self.mode {
+            hdr.result_flag = 1;
+        }
+        // tells the applet that an ErrorResultBacktrace storage follows
+        if let ErrorMode::ResultBacktrace { .. } = self.mode {
+            hdr.context_flag = 1;
+        }
+        hdr
+    }
+
+    fn language_code(&self) -> u64 {
+        self.language
+            .map(Ok)
+            .unwrap_or_else(Language::system)
+            .unwrap_or_default()
+            .code()
+    }
+
+    pub fn show(&self) -> Result<()> {
+        let hdr = self.header();
+        match self.mode {
+            ErrorMode::Result(code) => {
+                let arg = ErrorCommonArg {
+                    hdr,
+                    error_code: ErrorCode::default(),
+                    result: code.raw(),
+                };
+                launch(as_bytes(&arg), None)
+            }
+            ErrorMode::System {
+                code,
+                dialog_message,
+                fullscreen_message,
+            } => {
+                let mut cfg = ErrorSystemConfig::default();
+                cfg.arg.hdr = hdr;
+                cfg.arg.error_code = code.into();
+                cfg.arg.language_code = self.language_code();
+                copy_message_truncated(
+                    &mut cfg.arg.dialog_message,
+                    dialog_message,
+                    MessageField::Dialog,
+                );
+                if let Some(fullscreen_message) = fullscreen_message {
+                    copy_message_truncated(
+                        &mut cfg.arg.fullscreen_message,
+                        fullscreen_message,
+                        MessageField::Fullscreen,
+                    );
+                }
+                launch(as_bytes(&cfg.arg), None)
+            }
+            ErrorMode::Application {
+                dialog_message,
+                fullscreen_message,
+            } => {
+                let mut cfg = ErrorApplicationConfig::new(dialog_message, fullscreen_message);
+                cfg.arg.hdr = hdr;
+                if let Some(language) = self.language {
+                    cfg = cfg.language(language);
+                }
+                launch(as_bytes(&cfg.arg), None)
+            }
+            ErrorMode::ResultBacktrace { result, backtrace } => {
+                let arg = ErrorCommonArg {
+                    hdr,
+                    error_code: ErrorCode::default(),
+                    result: result.raw(),
+                };
+                let mut trace = ErrorResultBacktrace {
+                    count: 0,
+                    backtrace: [0; 0x20],
+                };
+                for (dst, src) in trace.backtrace.iter_mut().zip(backtrace) {
+                    *dst = src.raw();
+                    trace.count += 1;
+                }
+                launch(as_bytes(&arg), Some(as_bytes(&trace)))
+            }
+            ErrorMode::Eula(region) | ErrorMode::SystemUpdateEula(region) => {
+                let arg = ErrorEulaArg {
+                    hdr,
+                    region_code: region as u32,
+                };
+                launch(as_bytes(&arg), None)
+            }
+            ErrorMode::Record { code, timestamp } => {
+                let arg = ErrorRecordArg {
+                    hdr,
+                    error_code: code.into(),
+                    timestamp,
+                };
+                launch(as_bytes(&arg), None)
+            }
+        }
+    }
+}
+
+impl LibnxError {
+    /// Shows this error in the error applet. System results are shown with
+    /// their official code and help text, while errors with context show the
+    /// context chain (and backtrace, if one was captured) as custom text.
+    pub fn show(&self) -> Result<()> {
+        match self {
+            Self::System(_) => ErrorApplet::result(self.code()).show(),
+            Self::Context { .. } => {
+                let message = self.chain();
+                let details = self.backtrace().map(|trace| {
+                    let mut trace = trace.clone();
+                    trace.resolve();
+                    let header = format!("{}\nstack backtrace:\n", message);
+                    let max = MAX_MESSAGE_LEN.saturating_sub(header.len());
+                    header + &trace::format_to_fit(&trace::frames(&trace), max)
+                });
+                ErrorApplet::system(self.code(), &message, details.as_deref()).show()
+            }
+        }
     }
 }
@@ -0,0 +1,183 @@
+//! A `ConsoleRender` that draws into a `raw_fb::Framebuffer`. Unlike libnx's
+//! renderer, it draws fonts of any size, such as larger ones for TVs.
+//!
+//! ```ignore
+//! let font = BitmapFont::parse(&fs::read("romfs:/terminus-32.psf")?)?;
+//! let mut options = PrintConsole::default();
+//! options.font = font.console_font();
+//! let mut nwindow = NWindow::default();
+//! let render = FramebufferRender::new(&mut nwindow, 1920, 1080)?;
+//! let mut console = Console::with_renderer(options, render);
+//! console.init();
+//! ```
+
+use crate::console::{ConsoleRender, PrintConsole};
+use crate::raw_fb::{Buffering, Framebuffer, NWindow, PixelFormat};
+use crate::style::{Color, Style};
+use crate::{Result, ResultExt};
+
+type Rgba = [u8; 4];
+
+/// libnx's colours for each `Color`, then their bright versions for bold
+/// text.
+const PALETTE: [Rgba; 16] = [
+    [0, 0, 0, 255],
+    [128, 0, 0, 255],
+    [0, 128, 0, 255],
+    [128, 128, 0, 255],
+    [0, 0, 128, 255],
+    [128, 0, 128, 255],
+    [0, 128, 128, 255],
+    [192, 192, 192, 255],
+    [128, 128, 128, 255],
+    [255, 0, 0, 255],
+    [0, 255, 0, 255],
+    [255, 255, 0, 255],
+    [0, 0, 255, 255],
+    [255, 0, 255, 255],
+    [0, 255, 255, 255],
+    [255, 255, 255, 255],
+];
+
+/// Draws each glyph into a full tile of the console's font, in the console's
+/// colours. Italic and blinking text is drawn normally.
+pub struct FramebufferRender<'a> {
+    fb: Framebuffer<'a>,
+    width: usize,
+    height: usize,
+    /// Everything drawn so far, copied into the framebuffer on each flush,
+    /// since the framebuffer's buffers are swapped.
+    pixels: Vec<Rgba>,
+}
+
+impl<'a> FramebufferRender<'a> {
+    pub fn new(win: &'a mut NWindow<'_>, width: u32, height: u32) -> Result<Self> {
+        let mut fb = Framebuffer::new(win, width, height, PixelFormat::Rgba8888, Buffering::Double)
+            .context("creating framebuffer")?;
+        fb.make_linear().context("making framebuffer linear")?;
+        let (width, height) = (width as usize, height as usize);
+        Ok(Self {
+            fb,
+            width,
+            height,
+            pixels: vec![PALETTE[0]; width * height],
+        })
+    }
+
+    fn tile_size(console: &PrintConsole<'_>) -> (usize, usize) {
+        (
+            usize::from(console.font.tile_width()),
+            usize::from(console.font.tile_height()),
+        )
+    }
+
+    fn palette(index: i32, default: Color, bright: bool) -> Rgba {
+        let color = Color::from_index(index).unwrap_or(default).index() as usize;
+        PALETTE[if bright { color + 8 } else { color }]
+    }
+
+    /// The foreground and background colours for the console's style.
+    fn colors(console: &PrintConsole<'_>) -> (Rgba, Rgba) {
+        let style = Style::from_flags(console.flags);
+        let mut fg = Self::palette(console.fg, Color::White, style.contains(Style::BOLD));
+        let mut bg = Self::palette(console.bg, Color::Black, false);
+        if style.contains(Style::FAINT) {
+            for channel in &mut fg[..3] {
+                *channel /= 2;
+            }
+        }
+        if style.contains(Style::REVERSE) {
+            std::mem::swap(&mut fg, &mut bg);
+        }
+        if style.contains(Style::CONCEAL) {
+            fg = bg;
+        }
+        (fg, bg)
+    }
+}
+
+impl ConsoleRender for FramebufferRender<'_> {
+    /// Sizes the console and its window to fill the framebuffer.
+    fn init(&mut self, console: &mut PrintConsole<'_>) -> bool {
+        let (tile_width, tile_height) = Self::tile_size(console);
+        if tile_width == 0 || tile_height == 0 {
+            return false;
+        }
+        console.console_width = (self.width / tile_width) as i32;
+        console.console_height = (self.height / tile_height) as i32;
+        console.window_x = 0;
+        console.window_y = 0;
+        console.window_width = console.console_width;
+        console.window_height = console.console_height;
+        true
+    }
+
+    fn draw_char(&mut self, console: &PrintConsole<'_>, x: i32, y: i32, glyph: u32) {
+        if x < 0 || y < 0 {
+            return;
+        }
+        let (tile_width, tile_height) = Self::tile_size(console);
+        let (fg, bg) = Self::colors(console);
+        let style = Style::from_flags(console.flags);
+        let left = x as usize * tile_width;
+        let top = y as usize * tile_height;
+
+        for tile_y in 0..tile_height.min(self.height.saturating_sub(top)) {
+            let line = (style.contains(Style::UNDERLINE) && tile_y == tile_height - 1)
+                || (style.contains(Style::STRIKETHROUGH) && tile_y == tile_height / 2);
+            let row = (top + tile_y) * self.width;
+            for tile_x in 0..tile_width.min(self.width.saturating_sub(left)) {
+                let set = line || console.font.pixel(glyph, tile_x as u16, tile_y as u16);
+                self.pixels[row + left + tile_x] = if set { fg } else { bg };
+            }
+        }
+    }
+
+    fn scroll(&mut self, console: &PrintConsole<'_>) {
+        let (tile_width, tile_height) = Self::tile_size(console);
+        // the window in pixels, clipped to the framebuffer
+        let span = |start: i32, len: i32, tile: usize, max: usize| {
+            let start = start.max(0) as usize * tile;
+            let end = start + len.max(0) as usize * tile;
+            (start.min(max), end.min(max))
+        };
+        let (left, right) = span(
+            console.window_x,
+            console.window_width,
+            tile_width,
+            self.width,
+        );
+        let (top, bottom) = span(
+            console.window_y,
+            console.window_height,
+            tile_height,
+            self.height,
+        );
+        if tile_height == 0 || bottom - top < tile_height {
+            return;
+        }
+
+        for y in top..bottom - tile_height {
+            let from = (y + tile_height) * self.width;
+            let to = y * self.width;
+            self.pixels
+                .copy_within(from + left..from + right, to + left);
+        }
+        let bg = Self::palette(console.bg, Color::Black, false);
+        for y in bottom - tile_height..bottom {
+            let row = y * self.width;
+            for pixel in &mut self.pixels[row + left..row + right] {
+                *pixel = bg;
+            }
+        }
+    }
+
+    fn flush(&mut self, _console: &PrintConsole<'_>) {
+        let mut frame = self.fb.start_frame();
+        for (y, pixels) in self.pixels.chunks(self.width).enumerate() {
+            for (out, pixel) in frame.row_mut(y).chunks_mut(4).zip(pixels) {
+                out.copy_from_slice(pixel);
+            }
+        }
+    }
+}
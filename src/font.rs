@@ -0,0 +1,24 @@
+//! Bitmap fonts for the libnx console, from the `nx-font` crate.
+
+use crate::console::ConsoleFont;
+
+pub use nx_font::{min_len, words_per_row, BitmapFont, FontError};
+
+pub trait BitmapFontExt {
+    /// Borrows the font's glyph tiles for a console.
+    fn console_font(&self) -> ConsoleFont<'_>;
+}
+
+impl BitmapFontExt for BitmapFont {
+    fn console_font(&self) -> ConsoleFont<'_> {
+        // `BitmapFont` always allocates at least `min_len`
+        ConsoleFont::new(
+            self.gfx(),
+            self.ascii_offset(),
+            self.num_chars(),
+            self.tile_width(),
+            self.tile_height(),
+        )
+        .unwrap()
+    }
+}
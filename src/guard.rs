@@ -5,7 +5,7 @@ pub trait IntoLibnx<T> {
 #[macro_export]
 macro_rules! service_guard {
     ($name:ident) => {
-        service_guard!($name, ())
+        service_guard!($name, ());
     };
     ($name:ident, ($($arg_names:ident: $arg_types:ty as $ffi_types:ty),*)) => {
         paste::item! {
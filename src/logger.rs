@@ -0,0 +1,238 @@
+//! A `log` backend that sends each record to any number of outputs.
+//!
+//! ```ignore
+//! logger::Builder::new()
+//!     .level(LevelFilter::Info)
+//!     .module("libnx::raw_fb", LevelFilter::Trace)
+//!     .output(OpenOptions::new().create(true).append(true).open("sdmc:/app.log")?)
+//!     .output(nxlink::connect()?)
+//!     .install()?;
+//! ```
+//!
+//! Records for an `rs_console::Console` go through `console_channel`, and are
+//! drawn by the thread that owns the console:
+//!
+//! ```ignore
+//! let (output, lines) = logger::console_channel();
+//! logger::Builder::new().output(output).install()?;
+//! loop {
+//!     lines.draw(&mut console);
+//! }
+//! ```
+
+use crate::rs_console::{self, Rgb};
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+pub use ring_log::{RingLog, RING_LINE_LEN};
+use std::cmp;
+use std::fs::File;
+use std::io::Write;
+use std::net::TcpStream;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+
+/// Somewhere to send log records.
+pub trait Output: Send {
+    /// Writes one record. `line` is the formatted record, without a trailing
+    /// newline.
+    fn write(&mut self, record: &Record, line: &str);
+
+    fn flush(&mut self) {}
+}
+
+impl Output for File {
+    fn write(&mut self, _record: &Record, line: &str) {
+        let _ = writeln!(self, "{}", line);
+    }
+
+    fn flush(&mut self) {
+        let _ = Write::flush(self);
+    }
+}
+
+/// For a stream from `nxlink::connect`.
+impl Output for TcpStream {
+    fn write(&mut self, _record: &Record, line: &str) {
+        let _ = writeln!(self, "{}", line);
+    }
+
+    fn flush(&mut self) {
+        let _ = Write::flush(self);
+    }
+}
+
+/// The colour each level is drawn in on an `rs_console::Console`.
+pub fn level_color(level: Level) -> Rgb {
+    match level {
+        Level::Error => [255, 85, 85],
+        Level::Warn => [255, 215, 0],
+        Level::Info => rs_console::WHITE,
+        Level::Debug => [85, 205, 255],
+        Level::Trace => [150, 150, 150],
+    }
+}
+
+/// Sends records to an `rs_console::Console`. The console draws to the
+/// default window, so it can't be shared with the logger's threads; instead,
+/// the thread that owns it calls `ConsoleLines::draw`.
+pub struct ConsoleOutput(Sender<(Level, String)>);
+
+/// The lines sent by a `ConsoleOutput`.
+pub struct ConsoleLines(Receiver<(Level, String)>);
+
+pub fn console_channel() -> (ConsoleOutput, ConsoleLines) {
+    let (sender, receiver) = mpsc::channel();
+    (ConsoleOutput(sender), ConsoleLines(receiver))
+}
+
+impl Output for ConsoleOutput {
+    fn write(&mut self, record: &Record, line: &str) {
+        // fails once the `ConsoleLines` is dropped, when there's nowhere to
+        // show the line anyway
+        let _ = self.0.send((record.level(), line.to_string()));
+    }
+}
+
+impl ConsoleLines {
+    /// Appends the lines logged since the last call, returning whether there
+    /// were any.
+    pub fn draw(&self, console: &mut rs_console::Console) -> bool {
+        let mut any = false;
+        for (level, line) in self.0.try_iter() {
+            console.append_colored(&line, level_color(level));
+            any = true;
+        }
+        any
+    }
+}
+
+impl Output for Arc<RingLog> {
+    fn write(&mut self, _record: &Record, line: &str) {
+        self.push(line);
+    }
+}
+
+/// Per-module level filtering. The most specific module wins.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Filter {
+    default: LevelFilter,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Filter {
+    fn level(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module
+                    || (target.starts_with(module.as_str())
+                        && target[module.len()..].starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .iter()
+            .map(|&(_, level)| level)
+            .fold(self.default, cmp::max)
+    }
+}
+
+pub struct Logger {
+    filter: Filter,
+    outputs: Mutex<Vec<Box<dyn Output>>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.level(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let line = format!(
+            "[{:<5} {}] {}",
+            record.level(),
+            record.target(),
+            record.args()
+        );
+        if let Ok(mut outputs) = self.outputs.lock() {
+            for output in outputs.iter_mut() {
+                output.write(record, &line);
+            }
+        }
+    }
+
+    fn flush(&self) {
+        if let Ok(mut outputs) = self.outputs.lock() {
+            for output in outputs.iter_mut() {
+                output.flush();
+            }
+        }
+    }
+}
+
+pub struct Builder {
+    filter: Filter,
+    outputs: Vec<Box<dyn Output>>,
+}
+
+/// Logs `Info` and above, with no outputs.
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            filter: Filter {
+                default: LevelFilter::Info,
+                modules: vec![],
+            },
+            outputs: vec![],
+        }
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The level for modules without a level of their own.
+    pub fn level(mut self, level: LevelFilter) -> Self {
+        self.filter.default = level;
+        self
+    }
+
+    /// Sets the level for `module` and its submodules, such as
+    /// `"libnx::raw_fb"`.
+    pub fn module(mut self, module: impl Into<String>, level: LevelFilter) -> Self {
+        let module = module.into();
+        self.filter
+            .modules
+            .retain(|(existing, _)| *existing != module);
+        self.filter.modules.push((module, level));
+        self
+    }
+
+    pub fn output(mut self, output: impl Output + 'static) -> Self {
+        self.outputs.push(Box::new(output));
+        self
+    }
+
+    pub fn build(self) -> Logger {
+        Logger {
+            filter: self.filter,
+            outputs: Mutex::new(self.outputs),
+        }
+    }
+
+    /// Installs the logger. This fails if a logger is already installed.
+    pub fn install(self) -> Result<(), SetLoggerError> {
+        let logger = self.build();
+        let max_level = logger.filter.max_level();
+        log::set_boxed_logger(Box::new(logger))?;
+        log::set_max_level(max_level);
+        Ok(())
+    }
+}
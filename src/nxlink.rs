@@ -0,0 +1,26 @@
+use std::io;
+use std::net::{Ipv4Addr, SocketAddrV4, TcpStream};
+
+/// The port nxlink listens on for output from the console.
+pub const CLIENT_PORT: u16 = 28771;
+
+extern "C" {
+    static __nxlink_host: u32;
+}
+
+/// The address of the nxlink host that launched this program, if any.
+pub fn host() -> Option<Ipv4Addr> {
+    let raw = unsafe { __nxlink_host };
+    if raw == 0 {
+        None
+    } else {
+        Some(Ipv4Addr::from(u32::from_be(raw)))
+    }
+}
+
+/// Connects to the nxlink host. Sockets must already be initialized.
+pub fn connect() -> io::Result<TcpStream> {
+    let host = host()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "not launched through nxlink"))?;
+    TcpStream::connect(SocketAddrV4::new(host, CLIENT_PORT))
+}
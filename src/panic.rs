@@ -0,0 +1,238 @@
+use crate::console::Console;
+use crate::crash_report::{CrashReports, Report};
+use crate::error::{ErrorApplicationConfig, MAX_MESSAGE_LEN};
+use crate::logger::RingLog;
+use crate::nxlink;
+use crate::raw_fb::NWindow;
+use crate::rs_console;
+use crate::trace::{self, Frame, Verbosity};
+use backtrace::Backtrace;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::raw::c_int;
+use std::panic::{self, PanicInfo};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const DEFAULT_LOG: &str = "sdmc:/libnx-rs-panic.log";
+
+pub fn wait_for_button() {
+    extern "C" {
+        fn hidScanInput();
+        fn hidKeysDown(controller: c_int) -> u64;
+        fn appletMainLoop() -> bool;
+    }
+    unsafe {
+        while appletMainLoop() {
+            hidScanInput();
+            let down = hidKeysDown(10);
+            if down != 0 {
+                break;
+            }
+        }
+    }
+}
+
+/// Somewhere to send panic reports.
+///
+/// Logging sinks (`File`, `Nxlink` and `CrashReports`) all receive every report. Display sinks
+/// are tried in order until one succeeds, so later ones act as fallbacks.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Sink {
+    ErrorApplet,
+    /// The libnx console.
+    Console,
+    /// The framebuffer console from `rs_console`.
+    RsConsole,
+    File(PathBuf),
+    Nxlink,
+    /// A JSON report per panic, for tooling to ingest.
+    CrashReports(CrashReports),
+}
+
+impl Sink {
+    fn is_display(&self) -> bool {
+        match self {
+            Self::ErrorApplet | Self::Console | Self::RsConsole => true,
+            Self::File(_) | Self::Nxlink | Self::CrashReports(_) => false,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum BacktraceStyle {
+    Off,
+    /// Leaves out the panic machinery and the runtime, like
+    /// `RUST_BACKTRACE=1`.
+    Short,
+    /// Every frame, with its address.
+    Full,
+}
+
+#[derive(Debug, Clone)]
+pub struct Builder {
+    sinks: Vec<Sink>,
+    backtrace: BacktraceStyle,
+    wait_for_button: bool,
+    log: Option<Arc<RingLog>>,
+}
+
+/// Shows panics in the error applet, falling back to the libnx console,
+/// appends them to `DEFAULT_LOG`, and writes crash reports to
+/// `crash_report::DEFAULT_DIR`.
+impl Default for Builder {
+    fn default() -> Self {
+        Self {
+            sinks: vec![
+                Sink::ErrorApplet,
+                Sink::Console,
+                Sink::File(DEFAULT_LOG.into()),
+                Sink::CrashReports(CrashReports::default()),
+            ],
+            backtrace: BacktraceStyle::Full,
+            wait_for_button: true,
+            log: None,
+        }
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replaces the sinks.
+    pub fn sinks(mut self, sinks: impl IntoIterator<Item = Sink>) -> Self {
+        self.sinks = sinks.into_iter().collect();
+        self
+    }
+
+    pub fn sink(mut self, sink: Sink) -> Self {
+        self.sinks.push(sink);
+        self
+    }
+
+    pub fn backtrace(mut self, style: BacktraceStyle) -> Self {
+        self.backtrace = style;
+        self
+    }
+
+    /// Whether console sinks wait for a button press before returning, since
+    /// the program exits right after. Defaults to `true`.
+    pub fn wait_for_button(mut self, wait: bool) -> Self {
+        self.wait_for_button = wait;
+        self
+    }
+
+    /// Attaches the lines in `log` to crash reports.
+    pub fn log(mut self, log: Arc<RingLog>) -> Self {
+        self.log = Some(log);
+        self
+    }
+
+    pub fn install(self) {
+        panic::set_hook(Box::new(move |info| self.report(info)));
+    }
+
+    fn format_backtrace(&self, frames: &[Frame]) -> Option<String> {
+        match self.backtrace {
+            BacktraceStyle::Off => None,
+            BacktraceStyle::Short => Some(trace::format(frames, Verbosity::Short)),
+            BacktraceStyle::Full => Some(trace::format(frames, Verbosity::Full)),
+        }
+    }
+
+    fn wants_backtrace(&self) -> bool {
+        self.backtrace != BacktraceStyle::Off
+            || self
+                .sinks
+                .iter()
+                .any(|sink| matches!(sink, Sink::CrashReports(_)))
+    }
+
+    fn report(&self, info: &PanicInfo) {
+        let frames = if self.wants_backtrace() {
+            trace::frames(&Backtrace::new())
+        } else {
+            vec![]
+        };
+        let mut report = Report::new(info, frames);
+        if let Some(log) = &self.log {
+            log.for_each(|line| report.log.push(line.to_string()));
+        }
+
+        let mut short = format!(
+            "thread '{}' panicked at '{}'",
+            report.thread, report.payload
+        );
+        if let Some(location) = &report.location {
+            short += &format!(", {}:{}:{}", location.file, location.line, location.column);
+        }
+        let (long, fullscreen) = match self.format_backtrace(&report.backtrace) {
+            Some(trace) => {
+                let header = format!("{}\nstack backtrace:\n", short);
+                // the applet only has room for a summary, but the logs get
+                // everything
+                let max = MAX_MESSAGE_LEN.saturating_sub(header.len());
+                let summary = trace::format_to_fit(&report.backtrace, max);
+                (header.clone() + &trace, header + &summary)
+            }
+            None => (short.clone(), short.clone()),
+        };
+
+        // log first, in case displaying hangs
+        for sink in self.sinks.iter().filter(|sink| !sink.is_display()) {
+            let _ = match sink {
+                Sink::File(path) => OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .and_then(|mut log| writeln!(log, "{}\n", long)),
+                Sink::Nxlink => {
+                    nxlink::connect().and_then(|mut stream| writeln!(stream, "{}", long))
+                }
+                Sink::CrashReports(reports) => reports.write(&report).map(drop),
+                _ => Ok(()),
+            };
+        }
+
+        for sink in self.sinks.iter().filter(|sink| sink.is_display()) {
+            let shown = match sink {
+                Sink::ErrorApplet => ErrorApplicationConfig::new(&short, Some(&fullscreen))
+                    .show()
+                    .is_ok(),
+                Sink::Console => self.show_console(&long).is_ok(),
+                Sink::RsConsole => self.show_rs_console(&long).is_ok(),
+                _ => false,
+            };
+            if shown {
+                break;
+            }
+        }
+    }
+
+    fn show_console(&self, text: &str) -> io::Result<()> {
+        let mut console = Console::new();
+        writeln!(console, "{}", text)?;
+        console.update();
+        if self.wait_for_button {
+            wait_for_button();
+        }
+        Ok(())
+    }
+
+    fn show_rs_console(&self, text: &str) -> crate::Result<()> {
+        let mut nwindow = NWindow::default();
+        let mut console = rs_console::Console::new(&mut nwindow)?;
+        console.append(text);
+        if self.wait_for_button {
+            wait_for_button();
+        }
+        Ok(())
+    }
+}
+
+/// Installs the default panic hook.
+pub fn install() {
+    Builder::default().install();
+}
@@ -1,31 +1,156 @@
+use backtrace::Backtrace;
+use std::io;
 use std::num::NonZeroU32;
 use std::result::Result as StdResult;
 use thiserror::Error;
 
-include!(concat!(env!("OUT_DIR"), "/codegen.rs"));
+pub use nx_result::*;
 
-fn error_to_string(error: u32) -> &'static str {
-    ERROR_CODES.get(&error).copied().unwrap_or("Unknown error")
+fn describe(code: ResultCode) -> String {
+    let module = code
+        .module_name()
+        .map(|module| format!("{}: ", module))
+        .unwrap_or_default();
+    format!("{}{} ({}, {:#x})", module, code.message(), code, code)
 }
 
 #[derive(Error, Debug)]
 pub enum LibnxError {
-    #[error("{} ({0:x})", error_to_string(.0.get()))]
+    /// A failed result. Success can't be represented, so this is never an
+    /// error with code `2000-0000`.
+    #[error("{}", describe((*.0).into()))]
     System(NonZeroU32),
+    /// Only shows `message`, since the source is reported by
+    /// `Error::source`. `chain` shows both.
+    #[error("{message}")]
+    Context {
+        message: String,
+        source: Box<LibnxError>,
+        trace: Option<Backtrace>,
+    },
+}
+
+impl LibnxError {
+    pub fn code(&self) -> ResultCode {
+        match self {
+            Self::System(code) => (*code).into(),
+            Self::Context { source, .. } => source.code(),
+        }
+    }
+
+    /// The backtrace captured when context was first added to this error.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        match self {
+            Self::System(_) => None,
+            Self::Context { source, trace, .. } => source.backtrace().or(trace.as_ref()),
+        }
+    }
+
+    /// Wraps this error with a message describing what was being done.
+    pub fn context(self, message: impl Into<String>) -> Self {
+        // only the innermost context needs a backtrace
+        let trace = match self {
+            Self::System(_) => Some(Backtrace::new_unresolved()),
+            Self::Context { .. } => None,
+        };
+        Self::Context {
+            message: message.into(),
+            source: Box::new(self),
+            trace,
+        }
+    }
+
+    /// The message of this error and each of its sources, outermost first,
+    /// separated by colons.
+    pub fn chain(&self) -> String {
+        match self {
+            Self::System(_) => self.to_string(),
+            Self::Context {
+                message, source, ..
+            } => format!("{}: {}", message, source.chain()),
+        }
+    }
+
+    pub fn kind(&self) -> ResultKind {
+        self.code().kind()
+    }
+}
+
+fn io_error_kind(code: ResultCode) -> io::ErrorKind {
+    match code.kind() {
+        ResultKind::Kernel(KernelError::TimeOut) => io::ErrorKind::TimedOut,
+        ResultKind::Kernel(KernelError::OperationCanceled) => io::ErrorKind::Interrupted,
+        ResultKind::Kernel(KernelError::NoSuchEntry) => io::ErrorKind::NotFound,
+        ResultKind::Kernel(KernelError::PortRemoteDead)
+        | ResultKind::Kernel(KernelError::PortClosed) => io::ErrorKind::BrokenPipe,
+        ResultKind::Kernel(KernelError::InvalidSize)
+        | ResultKind::Kernel(KernelError::InvalidAddress)
+        | ResultKind::Kernel(KernelError::InvalidHandle)
+        | ResultKind::Kernel(KernelError::InvalidCombination)
+        | ResultKind::Kernel(KernelError::OutOfRange)
+        | ResultKind::Kernel(KernelError::InvalidEnum) => io::ErrorKind::InvalidInput,
+        ResultKind::Fs(FsError::PathDoesNotExist)
+        | ResultKind::Fs(FsError::FileNotFound)
+        | ResultKind::Fs(FsError::DirectoryNotFound)
+        | ResultKind::Fs(FsError::MountpointNotFound)
+        | ResultKind::Fs(FsError::MountNameNotFoundInTable)
+        | ResultKind::Fs(FsError::TitleIdNotFoundSavedataNotFound) => io::ErrorKind::NotFound,
+        ResultKind::Fs(FsError::PathAlreadyExists)
+        | ResultKind::Fs(FsError::FileDirectoryAlreadyExists)
+        | ResultKind::Fs(FsError::SpecifiedMountNameAlreadyExists) => io::ErrorKind::AlreadyExists,
+        ResultKind::Fs(FsError::PermissionDenied) => io::ErrorKind::PermissionDenied,
+        // fall back to the categories from `ERROR_RANGES`
+        ResultKind::Fs(error) => match error.description() {
+            4001..=4719 => io::ErrorKind::InvalidData,
+            6001..=6199 => io::ErrorKind::InvalidInput,
+            6400..=6499 => io::ErrorKind::PermissionDenied,
+            _ => io::ErrorKind::Other,
+        },
+        _ => io::ErrorKind::Other,
+    }
+}
+
+/// Maps known codes onto an `io::ErrorKind`, keeping the `LibnxError` as the
+/// inner error.
+impl From<LibnxError> for io::Error {
+    fn from(error: LibnxError) -> Self {
+        io::Error::new(io_error_kind(error.code()), error)
+    }
 }
 
 pub type Result<T> = StdResult<T, LibnxError>;
 
+pub trait ResultExt<T> {
+    fn context(self, message: impl Into<String>) -> Result<T>;
+
+    fn with_context<C: Into<String>>(self, f: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T> ResultExt<T> for Result<T> {
+    fn context(self, message: impl Into<String>) -> Result<T> {
+        self.map_err(|err| err.context(message))
+    }
+
+    fn with_context<C: Into<String>>(self, f: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|err| err.context(f()))
+    }
+}
+
 pub trait IntoResult<T> {
     fn into_result(self) -> Result<T>;
 }
 
-impl IntoResult<()> for u32 {
+impl IntoResult<()> for ResultCode {
     fn into_result(self) -> Result<()> {
-        if let Some(error) = NonZeroU32::new(self) {
-            Err(LibnxError::System(error))
-        } else {
-            Ok(())
+        match NonZeroU32::new(self.0) {
+            Some(code) => Err(LibnxError::System(code)),
+            None => Ok(()),
         }
     }
 }
+
+impl IntoResult<()> for u32 {
+    fn into_result(self) -> Result<()> {
+        ResultCode(self).into_result()
+    }
+}
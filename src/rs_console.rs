@@ -1,5 +1,5 @@
 use crate::raw_fb::{Buffering, Frame, Framebuffer, NWindow, PixelFormat};
-use crate::Result;
+use crate::{Result, ResultExt};
 use lru_time_cache::LruCache;
 use once_cell::sync::Lazy;
 use rusttype::{point, Font, GlyphId, PositionedGlyph, Scale};
@@ -22,9 +22,19 @@ static CHAR_PX: Lazy<usize> = Lazy::new(|| CHAR_WIDTH.ceil() as usize);
 static CHARS: Lazy<usize> = Lazy::new(|| ((WIDTH as f32) / *CHAR_WIDTH) as usize);
 const GLYPH_CACHE_SIZE: usize = 64; // randomly picked
 
+pub type Rgb = [u8; 3];
+pub const WHITE: Rgb = [255, 255, 255];
+
 type GlyphCache = LruCache<GlyphId, Vec<u8>>;
 
-fn draw_text(frame: &mut Frame, glyph_cache: &mut GlyphCache, text: &str, x: i32, y: i32) {
+fn draw_text(
+    frame: &mut Frame,
+    glyph_cache: &mut GlyphCache,
+    text: &str,
+    color: Rgb,
+    x: i32,
+    y: i32,
+) {
     let v_metrics = FONT.v_metrics(SCALE);
     let coords = point(x as f32, y as f32 + v_metrics.ascent);
 
@@ -40,9 +50,9 @@ fn draw_text(frame: &mut Frame, glyph_cache: &mut GlyphCache, text: &str, x: i32
                     break;
                 }
                 let pixel = frame.pixel_mut(x as _, y as _);
-                pixel[0] = *byte;
-                pixel[1] = *byte;
-                pixel[2] = *byte;
+                for (channel, &color) in pixel.iter_mut().zip(&color) {
+                    *channel = (u16::from(*byte) * u16::from(color) / 255) as u8;
+                }
                 pixel[3] = 255;
             }
         }
@@ -63,7 +73,7 @@ fn draw_glyph(glyph: &PositionedGlyph) -> Vec<u8> {
 
 pub struct Console<'a> {
     fb: Framebuffer<'a>,
-    lines: VecDeque<String>,
+    lines: VecDeque<(String, Rgb)>,
     changed: Vec<usize>,
     redraw: bool,
     glyph_cache: GlyphCache,
@@ -72,9 +82,9 @@ pub struct Console<'a> {
 
 impl<'a> Console<'a> {
     pub fn new(win: &'a mut NWindow<'_>) -> Result<Self> {
-        let mut fb =
-            Framebuffer::new(win, WIDTH, HEIGHT, PixelFormat::Rgba8888, Buffering::Double)?;
-        fb.make_linear()?;
+        let mut fb = Framebuffer::new(win, WIDTH, HEIGHT, PixelFormat::Rgba8888, Buffering::Double)
+            .context("creating framebuffer")?;
+        fb.make_linear().context("making framebuffer linear")?;
         let v_metrics = FONT.v_metrics(SCALE);
         let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
         let line_count = ((HEIGHT as f32) / line_height).floor() as usize;
@@ -99,18 +109,18 @@ impl<'a> Console<'a> {
         }
         let v_metrics = FONT.v_metrics(SCALE);
         let line_height = v_metrics.ascent - v_metrics.descent + v_metrics.line_gap;
-        for (i, line) in self.lines.iter().enumerate() {
+        for (i, (line, color)) in self.lines.iter().enumerate() {
             if !self.redraw && !self.changed.contains(&i) {
                 continue;
             }
             let y = (i as i32) * (line_height as i32);
-            draw_text(&mut frame, &mut self.glyph_cache, &line, 0, y);
+            draw_text(&mut frame, &mut self.glyph_cache, line, *color, 0, y);
         }
         self.redraw = false;
         self.changed.clear();
     }
 
-    fn push_wrapped_line(&mut self, line: &str) {
+    fn push_wrapped_line(&mut self, line: &str, color: Rgb) {
         debug_assert!(line.len() <= *CHARS, "{} > {}", line.len(), *CHARS);
         if self.lines.len() == self.line_count {
             self.lines.pop_front();
@@ -118,11 +128,11 @@ impl<'a> Console<'a> {
         } else if !self.redraw {
             self.changed.push(self.lines.len());
         }
-        self.lines.push_back(line.to_string());
+        self.lines.push_back((line.to_string(), color));
         debug_assert!(self.lines.len() <= self.line_count);
     }
 
-    fn push_one_line(&mut self, line: &str) {
+    fn push_one_line(&mut self, line: &str, color: Rgb) {
         debug_assert!(!line.contains('\n'));
         let mut chars = line.char_indices().peekable();
         let mut last_idx = 0;
@@ -131,12 +141,12 @@ impl<'a> Console<'a> {
             let end_idx = chars.peek();
             if let Some(&(end_idx, _)) = end_idx {
                 let slice = &line[last_idx..end_idx];
-                self.push_wrapped_line(slice);
+                self.push_wrapped_line(slice, color);
                 last_idx = end_idx;
             } else {
                 let slice = &line[last_idx..];
                 if slice.len() > 0 {
-                    self.push_wrapped_line(slice);
+                    self.push_wrapped_line(slice, color);
                 }
                 break;
             }
@@ -144,8 +154,12 @@ impl<'a> Console<'a> {
     }
 
     pub fn append(&mut self, text: &str) {
+        self.append_colored(text, WHITE);
+    }
+
+    pub fn append_colored(&mut self, text: &str, color: Rgb) {
         for line in text.split('\n') {
-            self.push_one_line(line);
+            self.push_one_line(line, color);
         }
         self.draw();
     }
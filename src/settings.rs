@@ -0,0 +1,162 @@
+use crate::result::*;
+use crate::service_guard;
+
+service_guard!(Set);
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Language {
+    Japanese = 0,
+    AmericanEnglish = 1,
+    French = 2,
+    German = 3,
+    Italian = 4,
+    Spanish = 5,
+    Chinese = 6,
+    Korean = 7,
+    Dutch = 8,
+    Portuguese = 9,
+    Russian = 10,
+    Taiwanese = 11,
+    BritishEnglish = 12,
+    CanadianFrench = 13,
+    LatinAmericanSpanish = 14,
+    SimplifiedChinese = 15,
+    TraditionalChinese = 16,
+    BrazilianPortuguese = 17,
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self::AmericanEnglish
+    }
+}
+
+const LANGUAGES: [Language; 18] = [
+    Language::Japanese,
+    Language::AmericanEnglish,
+    Language::French,
+    Language::German,
+    Language::Italian,
+    Language::Spanish,
+    Language::Chinese,
+    Language::Korean,
+    Language::Dutch,
+    Language::Portuguese,
+    Language::Russian,
+    Language::Taiwanese,
+    Language::BritishEnglish,
+    Language::CanadianFrench,
+    Language::LatinAmericanSpanish,
+    Language::SimplifiedChinese,
+    Language::TraditionalChinese,
+    Language::BrazilianPortuguese,
+];
+
+impl Language {
+    /// The IETF tag for this language, as used by the settings service.
+    pub fn tag(self) -> &'static str {
+        match self {
+            Self::Japanese => "ja",
+            Self::AmericanEnglish => "en-US",
+            Self::French => "fr",
+            Self::German => "de",
+            Self::Italian => "it",
+            Self::Spanish => "es",
+            Self::Chinese => "zh-CN",
+            Self::Korean => "ko",
+            Self::Dutch => "nl",
+            Self::Portuguese => "pt",
+            Self::Russian => "ru",
+            Self::Taiwanese => "zh-TW",
+            Self::BritishEnglish => "en-GB",
+            Self::CanadianFrench => "fr-CA",
+            Self::LatinAmericanSpanish => "es-419",
+            Self::SimplifiedChinese => "zh-Hans",
+            Self::TraditionalChinese => "zh-Hant",
+            Self::BrazilianPortuguese => "pt-BR",
+        }
+    }
+
+    /// The language code, which is the tag packed into a little-endian `u64`.
+    pub fn code(self) -> u64 {
+        let mut bytes = [0; 8];
+        let tag = self.tag().as_bytes();
+        bytes[..tag.len()].copy_from_slice(tag);
+        u64::from_le_bytes(bytes)
+    }
+
+    pub fn from_code(code: u64) -> Option<Self> {
+        LANGUAGES
+            .iter()
+            .copied()
+            .find(|language| language.code() == code)
+    }
+
+    /// Reads the system language from the settings service.
+    pub fn system() -> Result<Self> {
+        Set::new()?.system_language()
+    }
+}
+
+#[repr(u32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Region {
+    Japan = 0,
+    Usa = 1,
+    Europe = 2,
+    Australia = 3,
+    HongKongTaiwanKorea = 4,
+    China = 5,
+}
+
+impl Region {
+    pub fn from_code(code: u32) -> Option<Self> {
+        Some(match code {
+            0 => Self::Japan,
+            1 => Self::Usa,
+            2 => Self::Europe,
+            3 => Self::Australia,
+            4 => Self::HongKongTaiwanKorea,
+            5 => Self::China,
+            _ => return None,
+        })
+    }
+}
+
+impl Set {
+    pub fn system_language_code(&self) -> Result<u64> {
+        extern "C" {
+            fn setGetSystemLanguage(language_code: *mut u64) -> u32;
+        }
+
+        let mut code = 0;
+
+        unsafe {
+            setGetSystemLanguage(&mut code as *mut _).into_result()?;
+        }
+
+        Ok(code)
+    }
+
+    /// The system region, or `None` if the code isn't recognised, since
+    /// guessing could show the wrong EULA.
+    pub fn region_code(&self) -> Result<Option<Region>> {
+        extern "C" {
+            fn setGetRegionCode(out: *mut u32) -> u32;
+        }
+
+        let mut region: u32 = 0;
+
+        unsafe {
+            setGetRegionCode(&mut region as *mut _).into_result()?;
+        }
+
+        Ok(Region::from_code(region))
+    }
+
+    /// The system language, or the default if the code isn't recognised.
+    pub fn system_language(&self) -> Result<Language> {
+        Ok(Language::from_code(self.system_language_code()?).unwrap_or_default())
+    }
+}
@@ -0,0 +1,222 @@
+//! Colours and text styles for the libnx console, either set directly on a
+//! console or written as escape sequences.
+//!
+//! ```ignore
+//! use libnx::style::{styled, Color::*};
+//!
+//! println!("{} {}", styled("error:").fg(Red).bold(), message);
+//! ```
+
+use std::fmt;
+use std::ops::{BitOr, BitOrAssign};
+
+#[repr(i32)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Color {
+    Black = 0,
+    Red = 1,
+    Green = 2,
+    Yellow = 3,
+    Blue = 4,
+    Magenta = 5,
+    Cyan = 6,
+    White = 7,
+}
+
+impl Color {
+    /// The value of `PrintConsole::fg` or `bg` for this colour.
+    pub fn index(self) -> i32 {
+        self as i32
+    }
+
+    pub fn from_index(index: i32) -> Option<Self> {
+        Some(match index {
+            0 => Self::Black,
+            1 => Self::Red,
+            2 => Self::Green,
+            3 => Self::Yellow,
+            4 => Self::Blue,
+            5 => Self::Magenta,
+            6 => Self::Cyan,
+            7 => Self::White,
+            _ => return None,
+        })
+    }
+}
+
+/// A set of text styles, stored as the bits of `PrintConsole::flags`.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Style(i32);
+
+impl Style {
+    pub const NONE: Self = Self(0);
+    /// Also brightens the foreground colour.
+    pub const BOLD: Self = Self(1 << 0);
+    pub const FAINT: Self = Self(1 << 1);
+    pub const ITALIC: Self = Self(1 << 2);
+    pub const UNDERLINE: Self = Self(1 << 3);
+    pub const BLINK: Self = Self(1 << 4);
+    pub const REVERSE: Self = Self(1 << 6);
+    pub const CONCEAL: Self = Self(1 << 7);
+    pub const STRIKETHROUGH: Self = Self(1 << 8);
+
+    /// Each style with its SGR parameter.
+    const CODES: [(Self, u8); 8] = [
+        (Self::BOLD, 1),
+        (Self::FAINT, 2),
+        (Self::ITALIC, 3),
+        (Self::UNDERLINE, 4),
+        (Self::BLINK, 5),
+        (Self::REVERSE, 7),
+        (Self::CONCEAL, 8),
+        (Self::STRIKETHROUGH, 9),
+    ];
+
+    /// The style for `PrintConsole::flags`, ignoring bits that aren't styles.
+    pub fn from_flags(flags: i32) -> Self {
+        let all = Self::CODES.iter().fold(0, |all, (style, _)| all | style.0);
+        Self(flags & all)
+    }
+
+    pub fn flags(self) -> i32 {
+        self.0
+    }
+
+    pub fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl BitOr for Style {
+    type Output = Self;
+
+    fn bitor(self, other: Self) -> Self {
+        Self(self.0 | other.0)
+    }
+}
+
+impl BitOrAssign for Style {
+    fn bitor_assign(&mut self, other: Self) {
+        self.0 |= other.0;
+    }
+}
+
+/// Writes the escape sequence that switches to these colours and styles. It
+/// doesn't reset anything first, so combine it with `Reset` where needed.
+#[derive(Debug, Default, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Escape {
+    pub fg: Option<Color>,
+    pub bg: Option<Color>,
+    pub style: Style,
+}
+
+impl fmt::Display for Escape {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let codes = Style::CODES
+            .iter()
+            .filter(|&&(style, _)| self.style.contains(style))
+            .map(|&(_, code)| code)
+            .chain(self.fg.map(|fg| 30 + fg as u8))
+            .chain(self.bg.map(|bg| 40 + bg as u8));
+
+        let mut any = false;
+        for code in codes {
+            f.write_str(if any { ";" } else { "\x1b[" })?;
+            write!(f, "{}", code)?;
+            any = true;
+        }
+        if any {
+            f.write_str("m")?;
+        }
+        Ok(())
+    }
+}
+
+/// Resets the colours and styles to the console's defaults.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Reset;
+
+impl fmt::Display for Reset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("\x1b[0m")
+    }
+}
+
+/// A value displayed with colours and styles, created by `styled`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Styled<T> {
+    value: T,
+    escape: Escape,
+}
+
+pub fn styled<T: fmt::Display>(value: T) -> Styled<T> {
+    Styled {
+        value,
+        escape: Escape::default(),
+    }
+}
+
+impl<T> Styled<T> {
+    pub fn fg(mut self, color: Color) -> Self {
+        self.escape.fg = Some(color);
+        self
+    }
+
+    pub fn bg(mut self, color: Color) -> Self {
+        self.escape.bg = Some(color);
+        self
+    }
+
+    pub fn style(mut self, style: Style) -> Self {
+        self.escape.style |= style;
+        self
+    }
+
+    pub fn bold(self) -> Self {
+        self.style(Style::BOLD)
+    }
+
+    pub fn faint(self) -> Self {
+        self.style(Style::FAINT)
+    }
+
+    pub fn italic(self) -> Self {
+        self.style(Style::ITALIC)
+    }
+
+    pub fn underline(self) -> Self {
+        self.style(Style::UNDERLINE)
+    }
+
+    pub fn blink(self) -> Self {
+        self.style(Style::BLINK)
+    }
+
+    pub fn reverse(self) -> Self {
+        self.style(Style::REVERSE)
+    }
+
+    pub fn conceal(self) -> Self {
+        self.style(Style::CONCEAL)
+    }
+
+    pub fn strikethrough(self) -> Self {
+        self.style(Style::STRIKETHROUGH)
+    }
+}
+
+/// Resets everything afterwards, rather than restoring what was set before.
+impl<T: fmt::Display> fmt::Display for Styled<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.escape == Escape::default() {
+            return self.value.fmt(f);
+        }
+        write!(f, "{}", self.escape)?;
+        self.value.fmt(f)?;
+        write!(f, "{}", Reset)
+    }
+}
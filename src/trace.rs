@@ -0,0 +1,213 @@
+//! Backtrace formatting that trims the panic and runtime frames, and can fit a
+//! trace into the fixed-size messages of the error applet.
+
+use backtrace::Backtrace;
+use std::fmt::Write as _;
+
+/// Frames up to the last of these are the panic machinery.
+const PANIC_ENTRY: &[&str] = &[
+    "rust_begin_unwind",
+    "std::panicking::begin_panic",
+    "std::panicking::rust_panic",
+    "std::sys_common::backtrace::__rust_end_short_backtrace",
+    "std::sys::backtrace::__rust_end_short_backtrace",
+    "core::panicking::",
+    "core::result::unwrap_failed",
+    "core::option::expect_failed",
+    "core::option::unwrap_failed",
+];
+
+/// Everything from the first of these on is the runtime calling into `main`
+/// or a thread's closure.
+const RUNTIME: &[&str] = &[
+    "std::rt::lang_start",
+    "std::sys_common::backtrace::__rust_begin_short_backtrace",
+    "std::sys::backtrace::__rust_begin_short_backtrace",
+];
+
+/// This crate's modules which capture backtraces, so their frames are noise
+/// at the top of a trace.
+const CAPTURE_MODULES: &[&str] = &["panic", "crash_report", "result", "trace"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Frame {
+    pub ip: usize,
+    /// The demangled name, without the trailing hash.
+    pub symbol: Option<String>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl Frame {
+    fn name(&self) -> &str {
+        self.symbol.as_deref().unwrap_or("<unknown>")
+    }
+
+    fn location(&self) -> Option<String> {
+        let file = shorten_path(self.file.as_deref()?);
+        Some(match self.line {
+            Some(line) => format!("{}:{}", file, line),
+            None => file.to_string(),
+        })
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Verbosity {
+    /// Every frame, with addresses and source locations.
+    Full,
+    /// Trimmed frames with source locations.
+    Short,
+    /// Trimmed frames, names only.
+    Names,
+}
+
+/// The frames of a resolved backtrace. Inlined functions get a frame each.
+pub fn frames(trace: &Backtrace) -> Vec<Frame> {
+    let mut frames = vec![];
+    for frame in trace.frames() {
+        let ip = frame.ip() as usize;
+        if frame.symbols().is_empty() {
+            frames.push(Frame {
+                ip,
+                symbol: None,
+                file: None,
+                line: None,
+            });
+        }
+        for symbol in frame.symbols() {
+            frames.push(Frame {
+                ip,
+                symbol: symbol.name().map(|name| format!("{:#}", name)),
+                file: symbol.filename().map(|file| file.display().to_string()),
+                line: symbol.lineno(),
+            });
+        }
+    }
+    frames
+}
+
+fn is_panic_entry(name: &str) -> bool {
+    PANIC_ENTRY.iter().any(|prefix| name.starts_with(prefix))
+}
+
+fn is_runtime(name: &str) -> bool {
+    RUNTIME.iter().any(|prefix| name.starts_with(prefix))
+}
+
+fn is_capture(name: &str) -> bool {
+    let krate = module_path!().split("::").next().unwrap_or_default();
+    name.starts_with("backtrace::")
+        || CAPTURE_MODULES
+            .iter()
+            .any(|module| name.contains(&format!("{}::{}::", krate, module)))
+}
+
+/// `Result::unwrap` and friends, which call into the panic machinery.
+fn is_unwrap(name: &str) -> bool {
+    (name.starts_with("core::result::Result<") || name.starts_with("core::option::Option<"))
+        && (name.ends_with("::unwrap") || name.ends_with("::expect"))
+}
+
+/// Drops the frames that capture the backtrace or handle the panic, and the
+/// runtime frames below the entry point.
+pub fn trim(frames: &[Frame]) -> &[Frame] {
+    let mut end = frames
+        .iter()
+        .position(|frame| is_runtime(frame.name()))
+        .unwrap_or(frames.len());
+    while end > 0 && frames[end - 1].name().starts_with("core::ops::function::") {
+        end -= 1;
+    }
+    let frames = &frames[..end];
+
+    let start = match frames
+        .iter()
+        .rposition(|frame| is_panic_entry(frame.name()))
+    {
+        Some(entry) => entry + 1,
+        None => frames
+            .iter()
+            .rposition(|frame| is_capture(frame.name()))
+            .map_or(0, |capture| capture + 1),
+    };
+    let mut frames = &frames[start..];
+    while matches!(frames.first(), Some(frame) if is_unwrap(frame.name())) {
+        frames = &frames[1..];
+    }
+    frames
+}
+
+/// Makes paths relative to this crate, the cargo registry, or the Rust
+/// sources where possible.
+pub fn shorten_path(path: &str) -> &str {
+    if let Some(rest) = path.strip_prefix(concat!(env!("CARGO_MANIFEST_DIR"), "/")) {
+        return rest;
+    }
+    // registry sources are under the index's directory, and git sources are
+    // under the repository's directory and then the revision's
+    let prefixes = [
+        ("/.cargo/registry/src/", 1),
+        ("/.cargo/git/checkouts/", 2),
+        ("/rustc/", 1),
+    ];
+    for &(marker, skip) in &prefixes {
+        if let Some(i) = path.find(marker) {
+            let rest = &path[i + marker.len()..];
+            return rest.splitn(skip + 1, '/').nth(skip).unwrap_or(rest);
+        }
+    }
+    path
+}
+
+fn format_frame(out: &mut String, index: usize, frame: &Frame, verbosity: Verbosity) {
+    let _ = match verbosity {
+        Verbosity::Full => writeln!(out, "{:4}: {:#018x} - {}", index, frame.ip, frame.name()),
+        Verbosity::Short | Verbosity::Names => writeln!(out, "{:4}: {}", index, frame.name()),
+    };
+    if verbosity != Verbosity::Names {
+        if let Some(location) = frame.location() {
+            let _ = writeln!(out, "             at {}", location);
+        }
+    }
+}
+
+pub fn format(frames: &[Frame], verbosity: Verbosity) -> String {
+    let frames = match verbosity {
+        Verbosity::Full => frames,
+        Verbosity::Short | Verbosity::Names => trim(frames),
+    };
+    let mut out = String::new();
+    for (i, frame) in frames.iter().enumerate() {
+        format_frame(&mut out, i, frame, verbosity);
+    }
+    out
+}
+
+/// Formats the trimmed frames as verbosely as fits in `max` bytes. If even
+/// the names don't fit, the outermost frames are left out.
+pub fn format_to_fit(frames: &[Frame], max: usize) -> String {
+    for &verbosity in &[Verbosity::Short, Verbosity::Names] {
+        let out = format(frames, verbosity);
+        if out.len() <= max {
+            return out;
+        }
+    }
+
+    let frames = trim(frames);
+    let omitted = |count: usize| format!("      ... {} more\n", count);
+    let mut out = String::new();
+    for (i, frame) in frames.iter().enumerate() {
+        let mut line = String::new();
+        format_frame(&mut line, i, frame, Verbosity::Names);
+        if out.len() + line.len() + omitted(frames.len() - i - 1).len() > max {
+            let footer = omitted(frames.len() - i);
+            if out.len() + footer.len() <= max {
+                out += &footer;
+            }
+            break;
+        }
+        out += &line;
+    }
+    out
+}